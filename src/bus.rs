@@ -0,0 +1,242 @@
+//! # Multi-Drop RS-485 Bus
+//!
+//! A rack holds many Endzone 250 boards on one RS-485 bus, each with its own
+//! address. `Bus` owns one `Simulator` per board and routes every frame the way
+//! the shared wiring would: 'C' control frames go to the board named in their
+//! header, and data-load frames ('V', 'Q', 'P', ...), which carry no address, go
+//! to whichever board has the matching load session open.
+
+use crate::{CommandError, Endpoint, ProcessResult, Simulator};
+use std::time::Duration;
+
+/// A set of simulated boards sharing one bus.
+#[derive(Debug, Default, Clone)]
+pub struct Bus {
+    boards: Vec<Simulator>,
+}
+
+impl Bus {
+    /// Creates an empty bus.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a bus with one default board at each of the given addresses.
+    pub fn with_addresses(addresses: &[u8]) -> Self {
+        let mut bus = Self::new();
+        for &address in addresses {
+            bus.insert(Simulator::new(address));
+        }
+        bus
+    }
+
+    /// Adds a board to the bus. A board already at the same address is replaced
+    /// and returned.
+    pub fn insert(&mut self, simulator: Simulator) -> Option<Simulator> {
+        match self.boards.iter_mut().find(|b| b.rs485_address == simulator.rs485_address) {
+            Some(existing) => Some(std::mem::replace(existing, simulator)),
+            None => {
+                self.boards.push(simulator);
+                None
+            }
+        }
+    }
+
+    /// Removes and returns the board at `address`.
+    pub fn remove(&mut self, address: u8) -> Option<Simulator> {
+        let index = self.boards.iter().position(|b| b.rs485_address == address)?;
+        Some(self.boards.remove(index))
+    }
+
+    /// Returns the board at `address`.
+    pub fn board(&self, address: u8) -> Option<&Simulator> {
+        self.boards.iter().find(|b| b.rs485_address == address)
+    }
+
+    /// Returns the board at `address` for modification.
+    pub fn board_mut(&mut self, address: u8) -> Option<&mut Simulator> {
+        self.boards.iter_mut().find(|b| b.rs485_address == address)
+    }
+
+    /// All boards on the bus, in the order they were added.
+    pub fn boards(&self) -> &[Simulator] {
+        &self.boards
+    }
+
+    /// All boards on the bus, for modification.
+    pub fn boards_mut(&mut self) -> &mut [Simulator] {
+        &mut self.boards
+    }
+
+    /// The addresses of all boards on the bus.
+    pub fn addresses(&self) -> Vec<u8> {
+        self.boards.iter().map(|b| b.rs485_address).collect()
+    }
+
+    /// Returns the boards that would accept an unaddressed data-load frame of the given type.
+    fn loading_boards(&mut self, frame_type: u8) -> impl Iterator<Item = &mut Simulator> {
+        let is_pattern_frame = matches!(frame_type, b'P' | b'R');
+        self.boards.iter_mut().filter(move |b| {
+            if is_pattern_frame {
+                b.is_pattern_loading()
+            } else {
+                b.is_driver_loading()
+            }
+        })
+    }
+
+    /// Routes a frame to the board(s) it is meant for and returns the response.
+    ///
+    /// Only the addressed board answers a 'C' frame; a frame for an address with
+    /// no board on the bus is silently ignored, as it would be on real hardware.
+    pub fn process_command(&mut self, command_bytes: &[u8]) -> Result<ProcessResult, CommandError> {
+        let start_byte = command_bytes.iter().position(|&b| b == b'<');
+        let end_byte = command_bytes.iter().rposition(|&b| b == b'>');
+
+        let content_bytes = match (start_byte, end_byte) {
+            (Some(start), Some(end)) if end > start => &command_bytes[start + 1..end],
+            _ => return Err(CommandError::InvalidFrame),
+        };
+
+        if content_bytes.is_empty() {
+            return Err(CommandError::TooShort);
+        }
+
+        if content_bytes[0] == b'C' {
+            if content_bytes.len() < 5 {
+                return Err(CommandError::TooShort);
+            }
+            let addr_str = std::str::from_utf8(&content_bytes[1..3]).map_err(|_| CommandError::InvalidParameter)?;
+            let address = u8::from_str_radix(addr_str, 16).map_err(CommandError::InvalidAddress)?;
+            return match self.board_mut(address) {
+                Some(board) => board.process_command(command_bytes),
+                None => Ok(ProcessResult::default()),
+            };
+        }
+
+        // Data-load frames have no address; every board with an open session takes them.
+        // One board rejecting a frame must not keep it from the others, so the
+        // first error is only returned once all of them have seen it.
+        let mut combined = ProcessResult::default();
+        let mut first_error = None;
+        for board in self.loading_boards(content_bytes[0]) {
+            match board.process_command(command_bytes) {
+                Ok(result) => combined.logs.extend(result.logs),
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(combined),
+        }
+    }
+
+    /// Returns true if the board receiving pattern frames has two FPGAs.
+    pub fn has_dual_fpga(&self) -> bool {
+        self.boards.iter().find(|b| b.is_pattern_loading()).is_some_and(Simulator::has_dual_fpga)
+    }
+
+    /// Advances simulated time on every board by `elapsed`.
+    pub fn advance(&mut self, elapsed: Duration) {
+        for board in &mut self.boards {
+            board.advance(elapsed);
+        }
+    }
+}
+
+impl Endpoint for Bus {
+    fn process_command(&mut self, command_bytes: &[u8]) -> Result<ProcessResult, CommandError> {
+        Bus::process_command(self, command_bytes)
+    }
+
+    fn has_dual_fpga(&self) -> bool {
+        Bus::has_dual_fpga(self)
+    }
+
+    fn advance(&mut self, elapsed: Duration) {
+        Bus::advance(self, elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PatternMemory;
+
+    #[test]
+    fn only_addressed_board_answers() {
+        let mut bus = Bus::with_addresses(&[0x1F, 0x20]);
+
+        let result = bus.process_command(b"<C2003>").unwrap();
+        assert_eq!(result.response, Some(String::from("#ON#")));
+        assert!(bus.board(0x20).unwrap().sequence_on);
+        assert!(!bus.board(0x1F).unwrap().sequence_on);
+
+        // No board at 0x30: nobody answers.
+        let result = bus.process_command(b"<C3003>").unwrap();
+        assert_eq!(result.response, None);
+    }
+
+    #[test]
+    fn driver_data_goes_to_board_with_open_session() {
+        let mut bus = Bus::with_addresses(&[0x1F, 0x20, 0x21]);
+
+        bus.process_command(b"<C205002>").unwrap();
+        bus.process_command(b"<Txx0807060504030201>").unwrap();
+        let end = bus.process_command(b"<C205003>").unwrap();
+
+        assert_eq!(end.response, Some(format!("#{}#", 1 + 2 + 3 + 4 + 5 + 6 + 7 + 8)));
+        assert_eq!(bus.board(0x20).unwrap().timer_values, [1, 2, 3, 4]);
+        assert_eq!(bus.board(0x1F).unwrap().timer_values, [0; 4]);
+        assert_eq!(bus.board(0x21).unwrap().timer_values, [0; 4]);
+
+        // With the session closed, a stray data frame reaches no board.
+        bus.process_command(b"<Txx0909090909090909>").unwrap();
+        assert_eq!(bus.board(0x20).unwrap().timer_values, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn pattern_data_uses_loading_board_fpga_count() {
+        let mut bus = Bus::with_addresses(&[0x1F, 0x20]);
+        bus.board_mut(0x20).unwrap().fpgas[1].present = true;
+
+        bus.process_command(b"<C1F5000>").unwrap();
+        assert!(!bus.has_dual_fpga());
+        bus.process_command(b"<C1F5001>").unwrap();
+
+        bus.process_command(b"<C205000>").unwrap();
+        assert!(bus.has_dual_fpga());
+        bus.process_command(b"<P\x01\x02\x03\x04\x11\x12\x13\x14\xAA\x05\x06\x07\x08\x15\x16\x17\x18\xBB>").unwrap();
+        assert_eq!(bus.board(0x20).unwrap().fpgas[1].pattern_memory_a[1], 0x14131211);
+        assert_eq!(bus.board(0x1F).unwrap().fpgas[0].pattern_memory_a[1], 0);
+    }
+
+    #[test]
+    fn overflow_on_one_board_does_not_stop_the_others() {
+        let mut bus = Bus::with_addresses(&[0x1F, 0x20]);
+        bus.board_mut(0x1F).unwrap().fpgas[0].pattern_memory_a = PatternMemory::new(4);
+
+        bus.process_command(b"<C1F5000>").unwrap();
+        bus.process_command(b"<C205000>").unwrap();
+        let result = bus.process_command(b"<P\x01\x00\x00\x00\x00\x02\x00\x00\x00\x00\x03\x00\x00\x00\x00\x04\x00\x00\x00\x00>");
+
+        assert_eq!(result.unwrap_err(), CommandError::PatternMemoryOverflow { capacity: 3 });
+        assert_eq!(bus.board(0x20).unwrap().fpgas[0].pattern_memory_a.words(1..5), [1, 2, 3, 4]);
+        assert_eq!(bus.board(0x1F).unwrap().fpgas[0].pattern_memory_a.words(1..4), [0; 3]);
+    }
+
+    #[test]
+    fn insert_replaces_board_at_same_address() {
+        let mut bus = Bus::with_addresses(&[0x1F]);
+        let mut replacement = Simulator::new(0x1F);
+        replacement.bib_code = 0x123;
+
+        assert!(bus.insert(replacement).is_some());
+        assert_eq!(bus.boards().len(), 1);
+        assert_eq!(bus.board(0x1F).unwrap().bib_code, 0x123);
+        assert!(bus.remove(0x1F).is_some());
+        assert!(bus.boards().is_empty());
+    }
+}
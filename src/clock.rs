@@ -0,0 +1,179 @@
+//! # Simulated Time
+//!
+//! The board's behaviour over time (sequencing, ramps, PTC, timers, monitoring)
+//! is modelled as discrete events on a virtual clock. `Simulator::advance` moves
+//! the clock forward and fires every event that falls due on the way, in time
+//! order. Tests drive the clock directly for deterministic results; the binary
+//! uses `spawn_wall_clock` to advance it in step with real time.
+
+use crate::{Endpoint, Shared};
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{
+        atomic::{self, AtomicBool},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How often the firmware's main loop re-reads the monitor ADCs.
+pub const MONITOR_PERIOD: Duration = Duration::from_millis(100);
+
+/// How often `spawn_wall_clock` advances the simulated clock.
+pub const WALL_CLOCK_TICK: Duration = Duration::from_millis(10);
+
+/// Something the board does at a particular simulated time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum SimEvent {
+    /// A pass of the monitor loop, which refreshes the measured values.
+    MonitorCycle,
+    /// A PSU (0-based index) reaching one of its power-up steps (1-4).
+    PsuStep { psu: usize, step: u8 },
+    /// One micro-step of a PSU setpoint ramp towards `target`, with `remaining`
+    /// increments left including this one. `power_off` switches the PSU off at the end.
+    PsuRamp { psu: usize, target: f32, remaining: u32, power_off: bool },
+    /// The start of a PTC on period (`on: true`) or off period.
+    PtcPhase { on: bool },
+    /// An auto-reset restart of the sequence after a fault shutdown.
+    AutoReset,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Scheduled {
+    at: Duration,
+    // Breaks ties between events due at the same time in the order they were scheduled.
+    seq: u64,
+    event: SimEvent,
+}
+
+impl PartialEq for Scheduled {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Scheduled {}
+
+impl PartialOrd for Scheduled {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Scheduled {
+    // Reversed so the max-heap pops the earliest event first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.at, other.seq).cmp(&(self.at, self.seq))
+    }
+}
+
+/// A virtual clock and the queue of events waiting on it.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SimClock {
+    now: Duration,
+    next_seq: u64,
+    queue: BinaryHeap<Scheduled>,
+}
+
+impl SimClock {
+    /// Creates a clock at time zero with nothing scheduled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The current simulated time, measured from power-on.
+    pub fn now(&self) -> Duration {
+        self.now
+    }
+
+    /// Schedules `event` to fire `delay` from now. A zero delay fires it on the
+    /// next call to `pop_due`, after anything already due.
+    pub fn schedule(&mut self, delay: Duration, event: SimEvent) {
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.queue.push(Scheduled { at: self.now + delay, seq, event });
+    }
+
+    /// Removes every pending event for which `predicate` returns true.
+    pub fn cancel<F: FnMut(&SimEvent) -> bool>(&mut self, mut predicate: F) {
+        self.queue.retain(|scheduled| !predicate(&scheduled.event));
+    }
+
+    /// Returns true if any pending event matches `predicate`.
+    pub fn is_pending<F: FnMut(&SimEvent) -> bool>(&self, mut predicate: F) -> bool {
+        self.queue.iter().any(|scheduled| predicate(&scheduled.event))
+    }
+
+    /// Returns every pending event, in no particular order.
+    pub fn pending(&self) -> impl Iterator<Item = &SimEvent> {
+        self.queue.iter().map(|scheduled| &scheduled.event)
+    }
+
+    /// Pops the earliest event due at or before `until`, moving the clock to its time.
+    pub fn pop_due(&mut self, until: Duration) -> Option<SimEvent> {
+        if self.queue.peek()?.at > until {
+            return None;
+        }
+        let scheduled = self.queue.pop()?;
+        self.now = self.now.max(scheduled.at);
+        Some(scheduled.event)
+    }
+
+    /// Moves the clock to `time` without firing anything. Time never runs backwards.
+    pub fn set_now(&mut self, time: Duration) {
+        self.now = self.now.max(time);
+    }
+}
+
+/// Spawns a thread that advances `endpoint` in step with the wall clock until
+/// `stop` is set.
+pub fn spawn_wall_clock<T: Endpoint + 'static>(endpoint: Shared<T>, stop: Arc<AtomicBool>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut last = Instant::now();
+        while !stop.load(atomic::Ordering::Relaxed) {
+            thread::sleep(WALL_CLOCK_TICK);
+            let now = Instant::now();
+            endpoint.advance(now - last);
+            last = now;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fires_events_in_time_then_schedule_order() {
+        let mut clock = SimClock::new();
+        clock.schedule(Duration::from_millis(20), SimEvent::MonitorCycle);
+        clock.schedule(Duration::from_millis(10), SimEvent::MonitorCycle);
+        clock.schedule(Duration::from_millis(10), SimEvent::MonitorCycle);
+
+        assert_eq!(clock.pop_due(Duration::from_millis(5)), None);
+        assert_eq!(clock.now(), Duration::ZERO);
+
+        assert_eq!(clock.pop_due(Duration::from_millis(15)), Some(SimEvent::MonitorCycle));
+        assert_eq!(clock.now(), Duration::from_millis(10));
+        assert_eq!(clock.pop_due(Duration::from_millis(15)), Some(SimEvent::MonitorCycle));
+        assert_eq!(clock.pop_due(Duration::from_millis(15)), None);
+
+        clock.set_now(Duration::from_millis(15));
+        assert_eq!(clock.pop_due(Duration::from_millis(20)), Some(SimEvent::MonitorCycle));
+        assert_eq!(clock.now(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn cancelled_events_never_fire() {
+        let mut clock = SimClock::new();
+        clock.schedule(Duration::from_millis(10), SimEvent::MonitorCycle);
+        assert!(clock.is_pending(|e| *e == SimEvent::MonitorCycle));
+
+        clock.cancel(|e| *e == SimEvent::MonitorCycle);
+        assert!(!clock.is_pending(|e| *e == SimEvent::MonitorCycle));
+        assert_eq!(clock.pop_due(Duration::from_secs(1)), None);
+    }
+}
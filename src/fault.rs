@@ -0,0 +1,150 @@
+//! # Fault Injection
+//!
+//! Lets tests and tools force the fault conditions the firmware monitors, so a
+//! host's fault handling can be exercised without real hardware failing.
+//! Injected faults are held until cleared and are reported alongside any fault
+//! the simulated measurements produce on their own. `FaultSet` also describes
+//! the combined fault state the monitor loop sees.
+
+use serde::{Deserialize, Serialize};
+
+/// A fault condition that can be injected into a `Simulator`.
+///
+/// PSU, clock channel and sine-wave indices are 0-based, so `OverCurrent(0)`
+/// is PSU 1 and `ClockFailure(63)` is clock channel 64.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    OverCurrent(usize),
+    UnderVoltage(usize),
+    OverVoltage(usize),
+    ClockFailure(usize),
+    SineWaveFailure(usize),
+    DoorOpen,
+    TempNotOk,
+}
+
+/// Number of PSUs that can be faulted.
+pub const PSU_COUNT: usize = 6;
+/// Number of monitored clock channels (four modules of 16 channels).
+pub const CLOCK_CHANNEL_COUNT: usize = 64;
+/// Number of sine-wave modules.
+pub const SINE_WAVE_COUNT: usize = 2;
+
+/// A set of fault conditions, stored as one bit per PSU or channel.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FaultSet {
+    pub over_current: u8,
+    pub under_voltage: u8,
+    pub over_voltage: u8,
+    pub clock: u64,
+    pub sine_wave: u8,
+    pub door_open: bool,
+    pub temp_not_ok: bool,
+}
+
+impl FaultSet {
+    /// Returns true if `fault` names a PSU, channel or module that exists.
+    pub fn is_valid(fault: Fault) -> bool {
+        match fault {
+            Fault::OverCurrent(i) | Fault::UnderVoltage(i) | Fault::OverVoltage(i) => i < PSU_COUNT,
+            Fault::ClockFailure(i) => i < CLOCK_CHANNEL_COUNT,
+            Fault::SineWaveFailure(i) => i < SINE_WAVE_COUNT,
+            Fault::DoorOpen | Fault::TempNotOk => true,
+        }
+    }
+
+    /// Sets or clears a fault. `fault` must be valid.
+    pub fn set(&mut self, fault: Fault, active: bool) {
+        fn apply<T>(bits: &mut T, mask: T, active: bool)
+        where
+            T: Copy + std::ops::BitOr<Output = T> + std::ops::BitAnd<Output = T> + std::ops::Not<Output = T>,
+        {
+            *bits = if active { *bits | mask } else { *bits & !mask };
+        }
+
+        match fault {
+            Fault::OverCurrent(i) => apply(&mut self.over_current, 1 << i, active),
+            Fault::UnderVoltage(i) => apply(&mut self.under_voltage, 1 << i, active),
+            Fault::OverVoltage(i) => apply(&mut self.over_voltage, 1 << i, active),
+            Fault::ClockFailure(i) => apply(&mut self.clock, 1 << i, active),
+            Fault::SineWaveFailure(i) => apply(&mut self.sine_wave, 1 << i, active),
+            Fault::DoorOpen => self.door_open = active,
+            Fault::TempNotOk => self.temp_not_ok = active,
+        }
+    }
+
+    /// Returns true if `fault` is set.
+    pub fn contains(&self, fault: Fault) -> bool {
+        match fault {
+            Fault::OverCurrent(i) => self.over_current >> i & 1 == 1,
+            Fault::UnderVoltage(i) => self.under_voltage >> i & 1 == 1,
+            Fault::OverVoltage(i) => self.over_voltage >> i & 1 == 1,
+            Fault::ClockFailure(i) => self.clock >> i & 1 == 1,
+            Fault::SineWaveFailure(i) => self.sine_wave >> i & 1 == 1,
+            Fault::DoorOpen => self.door_open,
+            Fault::TempNotOk => self.temp_not_ok,
+        }
+    }
+
+    /// Returns true if no fault is set.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns true if any fault set here was not set in `previous`. The door is
+    /// not a fault condition in its own right and is ignored.
+    pub fn trips_since(&self, previous: &FaultSet) -> bool {
+        self.over_current & !previous.over_current != 0
+            || self.under_voltage & !previous.under_voltage != 0
+            || self.over_voltage & !previous.over_voltage != 0
+            || self.clock & !previous.clock != 0
+            || self.sine_wave & !previous.sine_wave != 0
+            || (self.temp_not_ok && !previous.temp_not_ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_and_clear_individual_faults() {
+        let mut faults = FaultSet::default();
+        faults.set(Fault::OverVoltage(5), true);
+        faults.set(Fault::ClockFailure(63), true);
+        faults.set(Fault::TempNotOk, true);
+
+        assert_eq!(faults.over_voltage, 0b10_0000);
+        assert_eq!(faults.clock, 1 << 63);
+        assert!(faults.contains(Fault::OverVoltage(5)));
+        assert!(!faults.contains(Fault::OverVoltage(4)));
+
+        faults.set(Fault::OverVoltage(5), false);
+        faults.set(Fault::ClockFailure(63), false);
+        faults.set(Fault::TempNotOk, false);
+        assert!(faults.is_empty());
+    }
+
+    #[test]
+    fn trips_only_on_newly_set_faults() {
+        let mut before = FaultSet::default();
+        before.set(Fault::ClockFailure(3), true);
+        let mut after = before.clone();
+        assert!(!after.trips_since(&before));
+
+        after.set(Fault::DoorOpen, true);
+        assert!(!after.trips_since(&before));
+        after.set(Fault::UnderVoltage(1), true);
+        assert!(after.trips_since(&before));
+        assert!(!before.trips_since(&after));
+    }
+
+    #[test]
+    fn rejects_out_of_range_indices() {
+        assert!(FaultSet::is_valid(Fault::OverCurrent(5)));
+        assert!(!FaultSet::is_valid(Fault::OverCurrent(6)));
+        assert!(!FaultSet::is_valid(Fault::ClockFailure(64)));
+        assert!(!FaultSet::is_valid(Fault::SineWaveFailure(2)));
+    }
+}
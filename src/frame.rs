@@ -0,0 +1,191 @@
+//! # Frame Decoder
+//!
+//! Reassembles `<...>` command frames from a raw byte stream. A serial read can
+//! return part of a frame, several frames, or line noise between frames, so
+//! transports push every chunk they receive into a `FrameDecoder` and pull
+//! complete frames back out in the order they arrived.
+
+/// The longest ASCII frame we are prepared to buffer before treating it as garbage.
+/// The longest real command ('I' or 'W') is well under this.
+const MAX_ASCII_FRAME_LEN: usize = 64;
+
+/// Returns the total length, including the `<` and `>` delimiters, of a binary
+/// 'P' or 'R' pattern frame. This mirrors the layout read by `handle_p_command`.
+pub fn pattern_frame_len(dual_fpga: bool) -> usize {
+    if dual_fpga {
+        // 'P' + two (FPGA1 word, FPGA2 word, control byte) groups = 19 content bytes.
+        19 + 2
+    } else {
+        // 'P' + four (word, control byte) groups = 21 content bytes.
+        21 + 2
+    }
+}
+
+/// Buffers incoming bytes and yields complete command frames.
+#[derive(Debug, Default, Clone)]
+pub struct FrameDecoder {
+    buffer: Vec<u8>,
+}
+
+impl FrameDecoder {
+    /// Creates an empty decoder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends raw bytes received from a transport.
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// Returns the number of bytes held waiting for the rest of a frame.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// Discards any partially received data.
+    pub fn clear(&mut self) {
+        self.buffer.clear();
+    }
+
+    /// Extracts the next complete frame, including its `<` and `>` delimiters.
+    ///
+    /// `dual_fpga` selects the expected length of binary 'P'/'R' frames, whose
+    /// payload may legitimately contain `<` or `>` bytes. It must match the
+    /// FPGA configuration of the simulator the frame is destined for.
+    pub fn next_frame(&mut self, dual_fpga: bool) -> Option<Vec<u8>> {
+        loop {
+            // Drop everything before the next start byte.
+            let start = match self.buffer.iter().position(|&b| b == b'<') {
+                Some(start) => start,
+                None => {
+                    self.buffer.clear();
+                    return None;
+                }
+            };
+            self.buffer.drain(..start);
+
+            if self.buffer.len() < 2 {
+                return None;
+            }
+
+            match self.buffer[1] {
+                b'P' | b'R' => {
+                    let frame_len = pattern_frame_len(dual_fpga);
+                    if self.buffer.len() < frame_len {
+                        return None;
+                    }
+                    if self.buffer[frame_len - 1] == b'>' {
+                        return Some(self.buffer.drain(..frame_len).collect());
+                    }
+                    // The length does not line up with a closing '>', so this was
+                    // not a real pattern frame. Resynchronise on the next '<'.
+                    self.buffer.drain(..1);
+                }
+                _ => {
+                    let delimiter = self.buffer[1..].iter().position(|&b| b == b'<' || b == b'>').map(|i| i + 1);
+                    match delimiter {
+                        Some(end) if self.buffer[end] == b'>' => {
+                            return Some(self.buffer.drain(..=end).collect());
+                        }
+                        Some(next_start) => {
+                            // A new frame started before this one closed; abandon the partial one.
+                            self.buffer.drain(..next_start);
+                        }
+                        None => {
+                            if self.buffer.len() > MAX_ASCII_FRAME_LEN {
+                                self.buffer.clear();
+                            }
+                            return None;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yields_single_complete_frame() {
+        let mut decoder = FrameDecoder::new();
+        decoder.push(b"<C1F03>");
+        assert_eq!(decoder.next_frame(false), Some(b"<C1F03>".to_vec()));
+        assert_eq!(decoder.next_frame(false), None);
+    }
+
+    #[test]
+    fn reassembles_frame_split_across_reads() {
+        let mut decoder = FrameDecoder::new();
+        decoder.push(b"<C1");
+        assert_eq!(decoder.next_frame(false), None);
+        decoder.push(b"F2");
+        assert_eq!(decoder.next_frame(false), None);
+        decoder.push(b"4>");
+        assert_eq!(decoder.next_frame(false), Some(b"<C1F24>".to_vec()));
+    }
+
+    #[test]
+    fn splits_multiple_frames_in_one_read() {
+        let mut decoder = FrameDecoder::new();
+        decoder.push(b"<C1F03><C1F24>\r\n<C1F04>");
+        assert_eq!(decoder.next_frame(false), Some(b"<C1F03>".to_vec()));
+        assert_eq!(decoder.next_frame(false), Some(b"<C1F24>".to_vec()));
+        assert_eq!(decoder.next_frame(false), Some(b"<C1F04>".to_vec()));
+        assert_eq!(decoder.next_frame(false), None);
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn drops_garbage_between_frames() {
+        let mut decoder = FrameDecoder::new();
+        decoder.push(b"noise>><C1F03>junk<C1F<C1F04>");
+        assert_eq!(decoder.next_frame(false), Some(b"<C1F03>".to_vec()));
+        // The unterminated "<C1F" is abandoned when a new frame starts.
+        assert_eq!(decoder.next_frame(false), Some(b"<C1F04>".to_vec()));
+        assert_eq!(decoder.next_frame(false), None);
+    }
+
+    #[test]
+    fn discards_overlong_unterminated_frame() {
+        let mut decoder = FrameDecoder::new();
+        decoder.push(b"<C");
+        decoder.push(&[b'0'; MAX_ASCII_FRAME_LEN]);
+        assert_eq!(decoder.next_frame(false), None);
+        assert_eq!(decoder.buffered(), 0);
+    }
+
+    #[test]
+    fn binary_pattern_frame_may_contain_delimiters_one_fpga() {
+        let mut decoder = FrameDecoder::new();
+        let frame = b"<P\x3C\x3E\x03\x04\x11\x3E\x06\x07\x08\x22\x09\x3C\x0B\x0C\x33\x0D\x0E\x0F\x10\x44>";
+        assert_eq!(frame.len(), pattern_frame_len(false));
+        decoder.push(&frame[..10]);
+        assert_eq!(decoder.next_frame(false), None);
+        decoder.push(&frame[10..]);
+        decoder.push(b"<C1F5001>");
+        assert_eq!(decoder.next_frame(false), Some(frame.to_vec()));
+        assert_eq!(decoder.next_frame(false), Some(b"<C1F5001>".to_vec()));
+    }
+
+    #[test]
+    fn binary_pattern_frame_two_fpgas() {
+        let mut decoder = FrameDecoder::new();
+        let frame = b"<R\x01\x3E\x03\x04\x11\x12\x3C\x14\xAA\x05\x06\x07\x08\x15\x16\x17\x18\xBB>";
+        assert_eq!(frame.len(), pattern_frame_len(true));
+        decoder.push(frame);
+        assert_eq!(decoder.next_frame(true), Some(frame.to_vec()));
+    }
+
+    #[test]
+    fn resynchronises_after_misaligned_pattern_frame() {
+        let mut decoder = FrameDecoder::new();
+        // A truncated 'P' frame followed by a control command.
+        decoder.push(b"<P\x01\x02<C1F5001>");
+        decoder.push(&[0u8; 16]);
+        assert_eq!(decoder.next_frame(false), Some(b"<C1F5001>".to_vec()));
+    }
+}
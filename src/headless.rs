@@ -0,0 +1,243 @@
+//! # Headless Mode
+//!
+//! Runs the simulator from command-line arguments without the TUI, so it can be
+//! started from scripts and CI. Traffic is printed to stdout or a log file, and
+//! SIGINT/SIGTERM stop the transport and exit cleanly.
+
+use ez_sim_lib::{
+    clock,
+    transport::{self, TransportMessage},
+    Bus, HardwareProfile, SharedBus,
+};
+use std::{
+    fs::File,
+    io::{self, Write},
+    net::TcpListener,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc,
+    },
+    thread,
+};
+
+pub const USAGE: &str = "\
+Usage: ez_sim                      Start the interactive TUI
+       ez_sim [OPTIONS] TRANSPORT  Run headless
+
+Transport (exactly one):
+  --serial PORT        Listen on a serial port
+  --tcp ADDR           Listen for TCP clients, e.g. 127.0.0.1:5000
+  --pty                Serve on a new pseudo-terminal (Unix only)
+
+Options:
+  -a, --address HEX    Board address(es), comma-separated or repeated (default: 1F)
+  --baud N             Serial baud rate (default: 115200)
+  --profile FILE       Fit the modules described in a hardware profile (TOML)
+  --log FILE           Write traffic to FILE instead of stdout
+  -h, --help           Show this help";
+
+const DEFAULT_BAUD_RATE: u32 = 115200;
+
+/// Where the headless simulator listens.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transport {
+    Serial { port: String, baud_rate: u32 },
+    Tcp(String),
+    Pty,
+}
+
+/// Settings for a headless run, parsed from the command line.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Options {
+    pub addresses: Vec<u8>,
+    pub transport: Transport,
+    pub log_file: Option<PathBuf>,
+    pub profile: Option<PathBuf>,
+}
+
+/// What the command line asked for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Invocation {
+    Run(Options),
+    Help,
+}
+
+fn parse_addresses(value: &str, addresses: &mut Vec<u8>) -> Result<(), String> {
+    for s in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        let address = u8::from_str_radix(s, 16).map_err(|_| format!("Invalid hex address '{}'.", s))?;
+        if addresses.contains(&address) {
+            return Err(format!("Address '{}' given more than once.", s));
+        }
+        addresses.push(address);
+    }
+    Ok(())
+}
+
+/// Parses the arguments that follow the program name.
+pub fn parse_args<I: IntoIterator<Item = String>>(args: I) -> Result<Invocation, String> {
+    let mut args = args.into_iter();
+    let mut addresses = Vec::new();
+    let mut transports = Vec::new();
+    let mut baud_rate = None;
+    let mut log_file = None;
+    let mut profile = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| args.next().ok_or_else(|| format!("Missing value for {}.", name));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Invocation::Help),
+            "-a" | "--address" => parse_addresses(&value(&arg)?, &mut addresses)?,
+            "--serial" => transports.push(Transport::Serial { port: value(&arg)?, baud_rate: DEFAULT_BAUD_RATE }),
+            "--tcp" => transports.push(Transport::Tcp(value(&arg)?)),
+            "--pty" => transports.push(Transport::Pty),
+            "--baud" => {
+                let s = value(&arg)?;
+                baud_rate = Some(s.parse::<u32>().map_err(|_| format!("Invalid baud rate '{}'.", s))?);
+            }
+            "--log" => log_file = Some(PathBuf::from(value(&arg)?)),
+            "--profile" => profile = Some(PathBuf::from(value(&arg)?)),
+            _ => return Err(format!("Unknown argument '{}'.", arg)),
+        }
+    }
+
+    if transports.len() != 1 {
+        return Err("Exactly one of --serial, --tcp or --pty is required.".to_string());
+    }
+    let mut transport = transports.remove(0);
+    match (&mut transport, baud_rate) {
+        (Transport::Serial { baud_rate: rate, .. }, Some(baud)) => *rate = baud,
+        (_, Some(_)) => return Err("--baud only applies to --serial.".to_string()),
+        _ => {}
+    }
+    if addresses.is_empty() {
+        addresses.push(0x1F);
+    }
+
+    Ok(Invocation::Run(Options { addresses, transport, log_file, profile }))
+}
+
+/// Runs the simulator until the transport fails or a termination signal arrives.
+pub fn run(options: Options) -> Result<(), Box<dyn std::error::Error>> {
+    let stop = Arc::new(AtomicBool::new(false));
+    for signal in [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM] {
+        signal_hook::flag::register(signal, stop.clone())?;
+    }
+
+    let mut out: Box<dyn Write> = match &options.log_file {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+
+    let addresses: Vec<_> = options.addresses.iter().map(|a| format!("0x{:02X}", a)).collect();
+    let mut bus = Bus::with_addresses(&options.addresses);
+    if let Some(path) = &options.profile {
+        let profile = HardwareProfile::load(path)?;
+        bus.boards_mut().iter_mut().try_for_each(|board| profile.apply(board))?;
+    }
+    let simulator = SharedBus::new(bus);
+    writeln!(out, "Simulator starting with Address(es): {}", addresses.join(", "))?;
+    if let Some(path) = &options.profile {
+        writeln!(out, "Hardware profile: {}", path.display())?;
+    }
+
+    let clock_thread = clock::spawn_wall_clock(simulator.clone(), stop.clone());
+
+    let (tx, rx) = mpsc::channel();
+    // Open the transport before spawning so a bad port or address fails immediately.
+    let listener = {
+        let stop = stop.clone();
+        match options.transport {
+            Transport::Serial { port, baud_rate } => {
+                let mut serial = serialport::new(&port, baud_rate).timeout(transport::READ_TIMEOUT).open()?;
+                writeln!(out, "Listening on {} at {} baud.", port, baud_rate)?;
+                thread::spawn(move || transport::serve(&mut serial, &simulator, &tx, &stop))
+            }
+            Transport::Tcp(address) => {
+                let tcp = TcpListener::bind(&address)?;
+                writeln!(out, "Listening for TCP clients on {}.", tcp.local_addr()?)?;
+                thread::spawn(move || transport::serve_tcp(tcp, simulator, tx, stop))
+            }
+            #[cfg(unix)]
+            Transport::Pty => {
+                let mut pty = ez_sim_lib::pty::Pty::open()?;
+                writeln!(out, "Serving on pseudo-terminal {}.", pty.slave_path().display())?;
+                thread::spawn(move || transport::serve(&mut pty, &simulator, &tx, &stop))
+            }
+            #[cfg(not(unix))]
+            Transport::Pty => return Err("pseudo-terminals are only available on Unix".into()),
+        }
+    };
+    out.flush()?;
+
+    // The channel disconnects once the listener thread has exited.
+    loop {
+        match rx.recv_timeout(transport::READ_TIMEOUT) {
+            Ok(TransportMessage::Log(line)) => writeln!(out, "{}", line)?,
+            Ok(TransportMessage::Error(e)) => writeln!(out, "[TRANSPORT ERROR] {}", e)?,
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        out.flush()?;
+    }
+
+    let result = listener.join().map_err(|_| "listener thread panicked")?;
+    // The clock must also stop when the transport fails on its own.
+    stop.store(true, Ordering::Relaxed);
+    clock_thread.join().map_err(|_| "clock thread panicked")?;
+    if result.is_ok() {
+        writeln!(out, "Stopped.")?;
+    }
+    out.flush()?;
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_serial_with_addresses() {
+        let invocation = parse_args(args("--serial /dev/ttyUSB0 --baud 9600 -a 1F,20 --address 21 --log out.txt")).unwrap();
+        assert_eq!(
+            invocation,
+            Invocation::Run(Options {
+                addresses: vec![0x1F, 0x20, 0x21],
+                transport: Transport::Serial { port: "/dev/ttyUSB0".to_string(), baud_rate: 9600 },
+                log_file: Some(PathBuf::from("out.txt")),
+                profile: None,
+            })
+        );
+    }
+
+    #[test]
+    fn defaults_to_address_1f() {
+        let invocation = parse_args(args("--tcp 127.0.0.1:5000 --profile board.toml")).unwrap();
+        assert_eq!(
+            invocation,
+            Invocation::Run(Options {
+                addresses: vec![0x1F],
+                transport: Transport::Tcp("127.0.0.1:5000".to_string()),
+                log_file: None,
+                profile: Some(PathBuf::from("board.toml")),
+            })
+        );
+        assert_eq!(parse_args(args("--pty --help")).unwrap(), Invocation::Help);
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        assert!(parse_args(args("-a 1F")).is_err());
+        assert!(parse_args(args("--tcp :5000 --pty")).is_err());
+        assert!(parse_args(args("--tcp :5000 --baud 9600")).is_err());
+        assert!(parse_args(args("--pty -a XY")).is_err());
+        assert!(parse_args(args("--pty -a 1F,1F")).is_err());
+        assert!(parse_args(args("--serial")).is_err());
+        assert!(parse_args(args("--pty --verbose")).is_err());
+    }
+}
@@ -4,7 +4,42 @@
 //! It manages the internal state of the simulated hardware and processes commands
 //! to modify that state, returning responses identical to the real hardware.
 
-use std::num::ParseIntError;
+use serde::{Deserialize, Serialize};
+use std::{num::ParseIntError, time::Duration};
+
+pub mod bus;
+pub mod clock;
+pub mod fault;
+pub mod frame;
+pub mod memory;
+pub mod profile;
+#[cfg(unix)]
+pub mod pty;
+pub mod report;
+pub mod sequencer;
+pub mod shared;
+pub mod snapshot;
+pub mod transport;
+pub mod vcd;
+
+pub use bus::Bus;
+pub use clock::{SimClock, SimEvent};
+pub use fault::{Fault, FaultSet};
+pub use frame::FrameDecoder;
+pub use memory::PatternMemory;
+pub use profile::{HardwareProfile, ProfileError};
+pub use report::{ConfigReport, ReportChange};
+pub use sequencer::{LoopError, Sequencer, Vector};
+pub use shared::{Endpoint, Shared, SharedBus, SharedSimulator};
+pub use snapshot::{SnapshotError, SNAPSHOT_VERSION};
+
+/// The default length of one count of the `T` command timers. The command
+/// carries no unit and the firmware's timing loop is not part of this model, so
+/// one minute is assumed; `Simulator::timer_unit` overrides it per board.
+pub const TIMER_UNIT: Duration = Duration::from_secs(60);
+
+/// How long auto-reset waits after a fault shutdown before restarting the sequence.
+pub const AUTO_RESET_DELAY: Duration = Duration::from_secs(1);
 
 // Custom error types for command processing.
 #[derive(Debug, PartialEq)]
@@ -21,6 +56,9 @@ pub enum CommandError {
     UnimplementedCommand(u8),
     /// The command is known, but has an invalid parameter.
     InvalidParameter,
+    /// A 'P' or 'R' frame would write past the end of FPGA memory. Nothing from
+    /// the frame is stored; `capacity` is the number of loadable addresses.
+    PatternMemoryOverflow { capacity: u32 },
 }
 
 /// The result of processing a command.
@@ -74,6 +112,7 @@ enum Command {
 }
 
 #[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 enum DataLoadMode {
     StartPatternLoad,
     EndPatternLoad,
@@ -82,7 +121,8 @@ enum DataLoadMode {
 }
 
 // Represents the state of a single Power Supply Unit (PSU).
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Psu {
     pub enabled: bool,
     pub voltage_setpoint: f32,
@@ -146,8 +186,38 @@ impl Default for Psu {
     }
 }
 
+impl Psu {
+    /// Returns the DAC setpoint loaded for power-up step 1-4, or 0 for any other step.
+    pub fn step_voltage(&self, step: u8) -> u16 {
+        match step {
+            1 => self.voltage_set_s1,
+            2 => self.voltage_set_s2,
+            3 => self.voltage_set_s3,
+            4 => self.voltage_set_s4,
+            _ => 0,
+        }
+    }
+
+    /// Converts a 12-bit DAC setpoint to the calibrated output voltage the
+    /// monitor reads back, before clamping at zero.
+    pub fn output_voltage(&self, setpoint: f32) -> f32 {
+        // The 0-4095 DAC range maps onto a 0-10V monitor ADC reading.
+        let raw_voltage_reading = setpoint / 409.5;
+        raw_voltage_reading * self.psu_cal_val + self.v_cal_offset_val
+    }
+}
+
+/// One of the two pattern/tristate memory banks of each FPGA.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MemoryBank {
+    #[default]
+    A,
+    B,
+}
+
 // Represents the state of an FPGA, including its pattern memory.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Fpga {
     pub present: bool,
     pub position: u8,
@@ -156,11 +226,10 @@ pub struct Fpga {
     pub mem_b_test_ok: bool,
     pub ctrl_a_test_ok: bool,
     pub ctrl_b_test_ok: bool,
-    // Using a Vec<u32> to represent pattern memory words.
-    pub pattern_memory_a: Vec<u32>,
-    pub pattern_memory_b: Vec<u32>,
-    pub tristate_memory_a: Vec<u32>,
-    pub tristate_memory_b: Vec<u32>,
+    pub pattern_memory_a: PatternMemory,
+    pub pattern_memory_b: PatternMemory,
+    pub tristate_memory_a: PatternMemory,
+    pub tristate_memory_b: PatternMemory,
 }
 
 impl Default for Fpga {
@@ -173,19 +242,62 @@ impl Default for Fpga {
             mem_b_test_ok: true,
             ctrl_a_test_ok: true,
             ctrl_b_test_ok: true,
-            // Pre-allocate memory to avoid resizing during data loading.
-            // 0x100000 corresponds to 1M addresses.
-            pattern_memory_a: vec![0; 0x100000],
-            pattern_memory_b: vec![0; 0x100000],
-            tristate_memory_a: vec![0; 0x100000],
-            tristate_memory_b: vec![0; 0x100000],
+            // 0x100000 corresponds to 1M addresses. Pages are allocated as
+            // data is loaded.
+            pattern_memory_a: PatternMemory::new(0x100000),
+            pattern_memory_b: PatternMemory::new(0x100000),
+            tristate_memory_a: PatternMemory::new(0x100000),
+            tristate_memory_b: PatternMemory::new(0x100000),
+        }
+    }
+}
+
+impl Fpga {
+    /// Returns the pattern memory of `bank`.
+    pub fn pattern_memory(&self, bank: MemoryBank) -> &PatternMemory {
+        match bank {
+            MemoryBank::A => &self.pattern_memory_a,
+            MemoryBank::B => &self.pattern_memory_b,
+        }
+    }
+
+    /// Returns the pattern memory of `bank` for writing.
+    pub fn pattern_memory_mut(&mut self, bank: MemoryBank) -> &mut PatternMemory {
+        match bank {
+            MemoryBank::A => &mut self.pattern_memory_a,
+            MemoryBank::B => &mut self.pattern_memory_b,
+        }
+    }
+
+    /// Returns the tristate memory of `bank`. A set bit disables the pin.
+    pub fn tristate_memory(&self, bank: MemoryBank) -> &PatternMemory {
+        match bank {
+            MemoryBank::A => &self.tristate_memory_a,
+            MemoryBank::B => &self.tristate_memory_b,
+        }
+    }
+
+    /// Returns the tristate memory of `bank` for writing.
+    pub fn tristate_memory_mut(&mut self, bank: MemoryBank) -> &mut PatternMemory {
+        match bank {
+            MemoryBank::A => &mut self.tristate_memory_a,
+            MemoryBank::B => &mut self.tristate_memory_b,
+        }
+    }
+
+    /// Zeroes both banks of pattern and tristate memory.
+    pub fn clear_memories(&mut self) {
+        for bank in [MemoryBank::A, MemoryBank::B] {
+            self.pattern_memory_mut(bank).clear();
+            self.tristate_memory_mut(bank).clear();
         }
     }
 }
 
 
 // Represents the state of a Clock Generator module.
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ClockGenerator {
     pub present: bool,
     pub enabled: bool,
@@ -197,7 +309,8 @@ pub struct ClockGenerator {
 }
 
 // Represents the state of a Sine Wave generator module.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SineWave {
     pub present: bool,
     pub enabled: bool,
@@ -215,8 +328,36 @@ pub struct SineWave {
     pub rms_value: f32,
 }
 
+impl SineWave {
+    /// Returns true if the module is enabled but cannot produce its output,
+    /// because it is absent or its FPGA is not programmed.
+    pub fn is_failing(&self) -> bool {
+        self.enabled && (!self.present || !self.programmed)
+    }
+
+    /// Returns the RMS output in volts for the parameters loaded by the `S` command.
+    ///
+    /// `amplitude` and `offset` are 12-bit DAC values on a 0-10V scale and
+    /// `duty_cycle` is the percentage of time the sine is gated on. A zero
+    /// `frequency_base` leaves only the DC offset. A disabled or failing module
+    /// outputs nothing.
+    pub fn simulated_rms(&self) -> f32 {
+        if !self.enabled || self.is_failing() {
+            return 0.0;
+        }
+        let offset = self.offset as f32 / 409.5;
+        if self.frequency_base == 0 {
+            return offset;
+        }
+        let peak = self.amplitude as f32 / 409.5;
+        let duty = self.duty_cycle.min(100) as f32 / 100.0;
+        (offset * offset + duty * peak * peak / 2.0).sqrt()
+    }
+}
+
 // Represents system-wide configuration and error handling settings.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SystemConfig {
     pub auto_reset: bool,
     pub auto_reset_retries: u32,
@@ -249,7 +390,8 @@ pub struct SystemConfig {
 }
 
 // Represents the Power Temperature Cycling (PTC) configuration.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PtcConfig {
     pub enabled: bool,
     pub on_time_seconds: u32,
@@ -257,7 +399,8 @@ pub struct PtcConfig {
 }
 
 // Represents the configuration for a single AMON/DUTMON test.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct AmonTest {
     pub test_type: u32,
     pub tp1_mux_ch: u32,
@@ -292,7 +435,8 @@ pub struct AmonTest {
 }
 
 // Represents the configuration for a single pattern loop.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct PatternLoop {
     pub start_address: u32,
     pub end_address: u32,
@@ -300,7 +444,8 @@ pub struct PatternLoop {
 }
 
 // Represents the main pattern clock configuration.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct MainClockConfig {
     pub freq_low_byte: u32,
     pub freq_high_byte: u32,
@@ -309,8 +454,16 @@ pub struct MainClockConfig {
     pub source: u32,
 }
 
+impl MainClockConfig {
+    /// Returns the 16-bit vector period, in ticks of the selected clock source.
+    pub fn period(&self) -> u32 {
+        (self.period_high_byte & 0xFF) << 8 | (self.period_low_byte & 0xFF)
+    }
+}
+
 // Represents the Fractional Clock (FRC) configuration.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FrcConfig {
     pub frequency_1_4: u32,
     pub frequency_5_8: u32,
@@ -321,7 +474,8 @@ pub struct FrcConfig {
 }
 
 /// Represents a snapshot of the system state at the time of a fault.
-#[derive(Debug, Default, Clone, PartialEq)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct FaultLog {
     pub monitor_voltages: [f32; 6],
     pub monitor_currents: [f32; 6],
@@ -342,7 +496,8 @@ pub struct FaultLog {
 }
 
 // The main struct that holds the entire state of the simulated driver board.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Simulator {
     // The 2-character hexadecimal RS-485 address of the simulator.
     pub rs485_address: u8,
@@ -370,10 +525,16 @@ pub struct Simulator {
     // Timer and Alarm values
     pub timer_values: [u32; 4],
     pub alarm_values: [u32; 4],
+    /// Set once a timer has counted down to its non-zero alarm threshold.
+    pub alarms_raised: [bool; 4],
+    /// The time one timer count lasts, `TIMER_UNIT` unless configured.
+    pub timer_unit: Duration,
     // System configuration
     pub system_config: SystemConfig,
     // Power Temperature Cycling configuration
     pub ptc_config: PtcConfig,
+    /// True while PTC has the PSUs switched off for its off period.
+    pub ptc_off_phase: bool,
     // AMON/DUTMON test configurations
     pub amon_tests: Vec<AmonTest>,
     pub amon_test_count: u32,
@@ -398,20 +559,49 @@ pub struct Simulator {
     pub door_open: bool, // C code uses 1 for closed, 0 for open
     // Historical fault logs
     pub fault_logs: Vec<FaultLog>,
+    /// The slot in `fault_logs` the next captured fault will be written to.
+    pub fault_log_index: usize,
+    /// The memory bank holding the running pattern: the bank of the last
+    /// completed `C50 00` load.
+    pub active_bank: MemoryBank,
     // --- Internal state for data loading sessions ---
     sram_address: u32,
     pattern_data_checksum: u32,
     driver_data_checksum: u32,
     is_pattern_data_loading: bool,
     is_driver_data_loading: bool,
+    // The bank the next pattern load session writes to, see `select_load_bank`.
+    next_load_bank: MemoryBank,
+    // The bank the open pattern load session writes to.
+    load_bank: MemoryBank,
+    // One past the last address of the last completed load of each bank.
+    pattern_end: [u32; 2],
     // --- Internal buffer for logging checksum changes ---
+    #[serde(skip)]
     log_buffer: Vec<String>,
+    // --- Simulated time and the events scheduled on it ---
+    clock: SimClock,
+    // Counting time not yet taken off the timers, always less than one `timer_unit`.
+    timer_carry: Duration,
+    // --- Faults forced on through `inject_fault` ---
+    injected_faults: FaultSet,
+    // The fault state seen by the previous monitor pass, to detect new faults.
+    last_faults: FaultSet,
+    // Clock channel failures seen by the monitor loop since the last `C01`.
+    clock_status_latch: u64,
+}
+
+impl Default for Simulator {
+    /// A board at the default RS-485 address 0x1F.
+    fn default() -> Self {
+        Self::new(0x1F)
+    }
 }
 
 impl Simulator {
     /// Creates a new `Simulator` instance with a given RS-485 address.
     pub fn new(rs485_address: u8) -> Self {
-        Self {
+        let mut simulator = Self {
             rs485_address,
             fw_version: 1.46,
             sequence_on: false,
@@ -428,8 +618,11 @@ impl Simulator {
             amon_bp: 0,
             timer_values: [0; 4],
             alarm_values: [0; 4],
+            alarms_raised: [false; 4],
+            timer_unit: TIMER_UNIT,
             system_config: Default::default(),
             ptc_config: Default::default(),
+            ptc_off_phase: false,
             amon_tests: vec![AmonTest::default(); 100], // Pre-allocate for 100 tests
             amon_test_count: 0,
             ustep_enabled: false,
@@ -446,15 +639,451 @@ impl Simulator {
             bp_res2_present: true,
             door_open: false, // Corresponds to 0 (closed) in C code
             fault_logs: vec![FaultLog::default(); 10], // C firmware stores 10 logs
+            fault_log_index: 0,
+            active_bank: MemoryBank::A,
             sram_address: 1,
             pattern_data_checksum: 0,
             driver_data_checksum: 0,
             is_pattern_data_loading: false,
             is_driver_data_loading: false,
+            next_load_bank: MemoryBank::A,
+            load_bank: MemoryBank::A,
+            pattern_end: [1; 2],
             log_buffer: Vec::new(),
+            clock: SimClock::new(),
+            timer_carry: Duration::ZERO,
+            injected_faults: FaultSet::default(),
+            last_faults: FaultSet::default(),
+            clock_status_latch: 0,
+        };
+        simulator.clock.schedule(clock::MONITOR_PERIOD, SimEvent::MonitorCycle);
+        simulator
+    }
+
+    /// The simulated time since power-on.
+    pub fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    /// Advances simulated time by `elapsed`, firing every event that falls due
+    /// on the way in the order it was scheduled.
+    pub fn advance(&mut self, elapsed: Duration) {
+        let target = self.clock.now() + elapsed;
+        while let Some(event) = self.clock.pop_due(target) {
+            self.handle_event(event);
+        }
+        self.clock.set_now(target);
+    }
+
+    /// Applies a single scheduled event at the current simulated time.
+    fn handle_event(&mut self, event: SimEvent) {
+        match event {
+            SimEvent::MonitorCycle => {
+                self.count_down_timers(clock::MONITOR_PERIOD);
+                self.update_monitored_values();
+                self.clock_status_latch |= self.live_clock_status();
+                let sw_status = self.live_sw_status();
+                for (i, sw) in self.sine_waves.iter_mut().enumerate() {
+                    sw.has_failure |= sw_status >> i & 1 == 1;
+                }
+                self.check_faults();
+                self.clock.schedule(clock::MONITOR_PERIOD, SimEvent::MonitorCycle);
+            }
+            SimEvent::PsuStep { psu, step } => {
+                self.psus[psu].enabled = true;
+                let target = self.psus[psu].step_voltage(step) as f32;
+                self.set_psu_voltage(psu, target, false);
+            }
+            SimEvent::AutoReset => {
+                self.system_config.auto_reset_counter += 1;
+                self.start_sequence();
+            }
+            SimEvent::PtcPhase { on: true } => {
+                self.ptc_off_phase = false;
+                self.start_power_up();
+                let on_time = Duration::from_secs(self.ptc_config.on_time_seconds as u64);
+                self.clock.schedule(on_time, SimEvent::PtcPhase { on: false });
+            }
+            SimEvent::PtcPhase { on: false } => {
+                self.ptc_off_phase = true;
+                self.power_down();
+                let off_time = Duration::from_secs(self.ptc_config.off_time_seconds as u64);
+                self.clock.schedule(off_time, SimEvent::PtcPhase { on: true });
+            }
+            SimEvent::PsuRamp { psu, target, remaining, power_off } => {
+                let psu_state = &mut self.psus[psu];
+                if remaining <= 1 {
+                    psu_state.voltage_setpoint = target;
+                    if power_off {
+                        psu_state.enabled = false;
+                    }
+                } else {
+                    psu_state.voltage_setpoint += (target - psu_state.voltage_setpoint) / remaining as f32;
+                    let delay = Duration::from_millis(psu_state.ustep_delay as u64);
+                    self.clock.schedule(delay, SimEvent::PsuRamp { psu, target, remaining: remaining - 1, power_off });
+                }
+            }
+        }
+    }
+
+    /// Forces a fault condition on until it is cleared with `clear_fault`.
+    pub fn inject_fault(&mut self, fault: Fault) -> Result<(), CommandError> {
+        if !FaultSet::is_valid(fault) {
+            return Err(CommandError::InvalidParameter);
+        }
+        self.injected_faults.set(fault, true);
+        Ok(())
+    }
+
+    /// Removes a previously injected fault.
+    pub fn clear_fault(&mut self, fault: Fault) -> Result<(), CommandError> {
+        if !FaultSet::is_valid(fault) {
+            return Err(CommandError::InvalidParameter);
+        }
+        self.injected_faults.set(fault, false);
+        Ok(())
+    }
+
+    /// Removes every injected fault.
+    pub fn clear_all_faults(&mut self) {
+        self.injected_faults = FaultSet::default();
+    }
+
+    /// The faults currently injected.
+    pub fn injected_faults(&self) -> &FaultSet {
+        &self.injected_faults
+    }
+
+    /// Returns true if the door reads open, either really or through an injected fault.
+    pub fn is_door_open(&self) -> bool {
+        self.door_open || self.injected_faults.door_open
+    }
+
+    /// Returns true if Temp_OK is set and no temperature fault is injected.
+    pub fn is_temp_ok(&self) -> bool {
+        self.temp_ok && !self.injected_faults.temp_not_ok
+    }
+
+    /// Returns the over-current, under-voltage and over-voltage flags, one bit per
+    /// PSU, from the measured values and any injected PSU faults.
+    pub fn psu_fault_flags(&self) -> (u8, u8, u8) {
+        let mut flags = (self.injected_faults.over_current, self.injected_faults.under_voltage, self.injected_faults.over_voltage);
+        for (i, psu) in self.psus.iter().enumerate() {
+            if psu.measured_current > psu.current_monitor_limit { flags.0 |= 1 << i; }
+            if psu.measured_voltage < psu.low_voltage_limit { flags.1 |= 1 << i; }
+            if psu.measured_voltage > psu.high_voltage_limit { flags.2 |= 1 << i; }
+        }
+        flags
+    }
+
+    /// Returns the 64 clock channel status bits as reported in `C24`, bit 0 being
+    /// channel 1. A set bit is a failure. Failures seen by the monitor loop stay
+    /// latched until `C01`, and nothing is reported while `ignore_clock_fails` is set.
+    pub fn clock_status(&self) -> u64 {
+        if self.system_config.ignore_clock_fails {
+            return 0;
+        }
+        self.clock_status_latch | self.live_clock_status()
+    }
+
+    /// Returns the clock channels failing right now, after the monitor filters.
+    ///
+    /// Each clock module drives 16 channels: module 1 is channels 1-16 and so on.
+    /// A present module with a failure fails all its channels, as does a missing
+    /// module when `clocks_required` is set. A set bit in `clk32_mon_filter`
+    /// (channels 1-32) or `clk64_mon_filter` (channels 33-64) masks the channel.
+    fn live_clock_status(&self) -> u64 {
+        let mut raw = self.injected_faults.clock;
+        for (i, gen) in self.clock_generators.iter().enumerate() {
+            let failing = if gen.present { gen.has_failure } else { self.system_config.clocks_required };
+            if failing {
+                raw |= 0xFFFF << (16 * i);
+            }
+        }
+        let filter = self.system_config.clk32_mon_filter as u64 | (self.system_config.clk64_mon_filter as u64) << 32;
+        raw & !filter
+    }
+
+    /// Returns the sine-wave status bits: bit 0 for SW1 and bit 1 for SW2 failing.
+    /// Failures seen by the monitor loop stay set in `has_failure` until `C02`.
+    pub fn sw_status(&self) -> u32 {
+        let mut status = self.live_sw_status();
+        for (i, sw) in self.sine_waves.iter().enumerate() {
+            if sw.has_failure { status |= 1 << i; }
+        }
+        status
+    }
+
+    /// Returns the sine-wave modules failing right now, including injected faults.
+    fn live_sw_status(&self) -> u32 {
+        let mut status = self.injected_faults.sine_wave as u32;
+        for (i, sw) in self.sine_waves.iter().enumerate() {
+            if sw.is_failing() { status |= 1 << i; }
+        }
+        status
+    }
+
+    /// Returns every fault condition currently present: measured PSU limit
+    /// violations, clock and sine-wave failures and the door, including injected
+    /// faults. A temperature fault only comes from injection; Temp_OK cleared by
+    /// `C16` just means the chamber is not at temperature yet.
+    pub fn active_faults(&self) -> FaultSet {
+        let (over_current, under_voltage, over_voltage) = self.psu_fault_flags();
+        FaultSet {
+            over_current,
+            under_voltage,
+            over_voltage,
+            clock: self.clock_status(),
+            sine_wave: self.sw_status() as u8,
+            door_open: self.is_door_open(),
+            temp_not_ok: self.injected_faults.temp_not_ok,
         }
     }
 
+    /// Returns true while faults are monitored: the sequence is on, the PSUs are
+    /// powered and no step or ramp is still in progress.
+    fn is_monitoring_armed(&self) -> bool {
+        self.sequence_on
+            && !self.ptc_off_phase
+            && !self.clock.is_pending(|e| matches!(e, SimEvent::PsuStep { .. } | SimEvent::PsuRamp { .. }))
+    }
+
+    /// Looks for newly tripped faults, recording a fault log entry when one
+    /// appears, and shuts the sequence down if a stop-on-error flag covers it.
+    fn check_faults(&mut self) {
+        if !self.is_monitoring_armed() {
+            self.last_faults = FaultSet::default();
+            return;
+        }
+        let faults = self.active_faults();
+        if faults.trips_since(&self.last_faults) {
+            self.capture_fault_log();
+        }
+        self.last_faults = faults;
+        if self.stops_on(&self.last_faults) {
+            self.shut_down_on_fault();
+        }
+    }
+
+    /// Returns true if any of `faults` is covered by a stop-on-error flag from the 'E' command.
+    fn stops_on(&self, faults: &FaultSet) -> bool {
+        let config = &self.system_config;
+        (config.stop_on_v_error && (faults.under_voltage | faults.over_voltage) != 0)
+            || (config.stop_on_i_error && faults.over_current != 0)
+            || (config.stop_on_clk_error && faults.clock != 0)
+            || (config.stop_on_temp_error && faults.temp_not_ok)
+    }
+
+    /// Turns the sequence off after a fault, as `C04` would. With auto-reset
+    /// enabled, a restart is scheduled until `auto_reset_retries` restarts have
+    /// been used up.
+    fn shut_down_on_fault(&mut self) {
+        self.stop_ptc();
+        self.power_down();
+        self.sequence_on = false;
+
+        let config = &self.system_config;
+        if config.auto_reset && config.auto_reset_counter < config.auto_reset_retries {
+            self.clock.schedule(AUTO_RESET_DELAY, SimEvent::AutoReset);
+        }
+    }
+
+    /// Turns the sequence on: the PSUs with a non-zero step 4 voltage (loaded by
+    /// a 'V' command) are brought up over time in the configured order, and with
+    /// PTC enabled they are then cycled off and on again.
+    fn start_sequence(&mut self) {
+        self.stop_ptc();
+        self.start_power_up();
+        if self.is_ptc_cycling() {
+            let on_time = Duration::from_secs(self.ptc_config.on_time_seconds as u64);
+            self.clock.schedule(on_time, SimEvent::PtcPhase { on: false });
+        }
+        self.sequence_on = true;
+    }
+
+    /// Snapshots the monitored state into the next fault log slot, mimicking the
+    /// firmware's rotating ten-entry history.
+    fn capture_fault_log(&mut self) {
+        let (over_current_flags, under_voltage_flags, over_voltage_flags) = self.psu_fault_flags();
+        let clock_status = self.clock_status();
+        let log = FaultLog {
+            monitor_voltages: std::array::from_fn(|i| self.psus[i].measured_voltage),
+            monitor_currents: std::array::from_fn(|i| self.psus[i].measured_current),
+            auto_reset_counter: self.system_config.auto_reset_counter,
+            over_current_flags,
+            under_voltage_flags,
+            over_voltage_flags,
+            clock_status_1_16: clock_status as u16,
+            clock_status_17_32: (clock_status >> 16) as u16,
+            clock_status_33_48: (clock_status >> 32) as u16,
+            clock_status_49_64: (clock_status >> 48) as u16,
+            sw_fault_status: self.sw_status(),
+            sw1_rms: self.sine_waves[0].rms_value,
+            sw2_rms: self.sine_waves[1].rms_value,
+            driver_on: self.sequence_on && !self.ptc_off_phase,
+            timer_values: self.timer_values,
+            alarm_values: self.alarm_values,
+        };
+        let slot = self.fault_log_index % self.fault_logs.len();
+        self.fault_logs[slot] = log;
+        self.fault_log_index = (slot + 1) % self.fault_logs.len();
+    }
+
+    /// Counts the timers down by `elapsed`, mimicking the firmware's timing loop.
+    /// Time only counts while Temp_OK is set and the sequence is on; each timer
+    /// stops at zero. An alarm value is taken as the remaining time at which to
+    /// warn that its timer is about to expire, so an alarm is raised once the
+    /// timer has counted down to it. An alarm value of 0 disables the alarm.
+    fn count_down_timers(&mut self, elapsed: Duration) {
+        if !(self.is_temp_ok() && self.sequence_on) || self.timer_unit.is_zero() {
+            return;
+        }
+        self.timer_carry += elapsed;
+        while self.timer_carry >= self.timer_unit {
+            self.timer_carry -= self.timer_unit;
+            for timer in self.timer_values.iter_mut() {
+                *timer = timer.saturating_sub(1);
+            }
+        }
+        for ((raised, &timer), &alarm) in self.alarms_raised.iter_mut().zip(&self.timer_values).zip(&self.alarm_values) {
+            if alarm > 0 && timer <= alarm {
+                *raised = true;
+            }
+        }
+    }
+
+    /// Returns true if PSU `psu` (0-based) ramps its setpoint in micro-steps.
+    fn is_micro_stepping(&self, psu: usize) -> bool {
+        self.ustep_enabled && self.psus[psu].ustep_steps > 0
+    }
+
+    /// Moves a PSU's setpoint to `target`. With micro-stepping configured the
+    /// setpoint ramps there in `ustep_steps` equal increments, each applied
+    /// `ustep_delay` ms after the one before; otherwise it is set at once.
+    /// `power_off` switches the PSU off once the target is reached.
+    fn set_psu_voltage(&mut self, psu: usize, target: f32, power_off: bool) {
+        self.clock.cancel(|e| matches!(e, SimEvent::PsuRamp { psu: p, .. } if *p == psu));
+        if self.is_micro_stepping(psu) {
+            let psu_state = &self.psus[psu];
+            let delay = Duration::from_millis(psu_state.ustep_delay as u64);
+            self.clock.schedule(delay, SimEvent::PsuRamp { psu, target, remaining: psu_state.ustep_steps, power_off });
+        } else {
+            self.psus[psu].voltage_setpoint = target;
+            if power_off {
+                self.psus[psu].enabled = false;
+            }
+        }
+    }
+
+    /// Cancels every scheduled PSU step and ramp.
+    fn cancel_power_events(&mut self) {
+        self.clock.cancel(|e| matches!(e, SimEvent::PsuStep { .. } | SimEvent::PsuRamp { .. }));
+    }
+
+    /// Schedules the staged power-up that follows `C03`, mimicking `Sequence_ON`.
+    ///
+    /// Every PSU takes part, as in the firmware; one whose step voltage is unset
+    /// is enabled and ramps to 0 V. With `psu_step_enabled`
+    /// each PSU is brought through steps 1-4, waiting `psu_step_delay` ms between
+    /// steps; otherwise it goes straight to step 4. Within each step, if
+    /// `psu_sequence_enabled` is set, PSUs come up in `sequence_id` order, each
+    /// waiting its own `sequence_delay` ms after the previous one.
+    fn start_power_up(&mut self) {
+        self.cancel_power_events();
+
+        let order = self.power_up_order();
+        for psu in self.psus.iter_mut() {
+            psu.enabled = false;
+            psu.voltage_setpoint = 0.0;
+        }
+
+        let steps: &[u8] = if self.system_config.psu_step_enabled { &[1, 2, 3, 4] } else { &[4] };
+        let mut at = Duration::ZERO;
+        for (n, &step) in steps.iter().enumerate() {
+            if n > 0 {
+                at += Duration::from_millis(self.system_config.psu_step_delay as u64);
+            }
+            for &psu in &order {
+                if self.system_config.psu_sequence_enabled {
+                    at += Duration::from_millis(self.psus[psu].sequence_delay as u64);
+                }
+                self.clock.schedule(at, SimEvent::PsuStep { psu, step });
+            }
+        }
+    }
+
+    /// Returns the 0-based PSUs in the order `C03` powers them up: all of them,
+    /// sorted by `sequence_id` when `psu_sequence_enabled` is set.
+    pub fn power_up_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.psus.len()).collect();
+        if self.system_config.psu_sequence_enabled {
+            order.sort_by_key(|&i| self.psus[i].sequence_id);
+        }
+        order
+    }
+
+    /// Returns true if Power Temperature Cycling should run while the sequence is on.
+    fn is_ptc_cycling(&self) -> bool {
+        self.ptc_config.enabled && self.ptc_config.on_time_seconds > 0 && self.ptc_config.off_time_seconds > 0
+    }
+
+    /// Stops any PTC cycle in progress.
+    fn stop_ptc(&mut self) {
+        self.clock.cancel(|e| matches!(e, SimEvent::PtcPhase { .. }));
+        self.ptc_off_phase = false;
+    }
+
+    /// Cancels any power-up still in progress and switches every PSU off,
+    /// ramping micro-stepped PSUs down to zero first.
+    fn power_down(&mut self) {
+        self.cancel_power_events();
+        for psu in 0..self.psus.len() {
+            if self.psus[psu].enabled && self.psus[psu].voltage_setpoint > 0.0 {
+                self.set_psu_voltage(psu, 0.0, true);
+            } else {
+                self.psus[psu].enabled = false;
+                self.psus[psu].voltage_setpoint = 0.0;
+            }
+        }
+    }
+
+    /// Returns true while a `C50 00` pattern load session is open.
+    pub fn is_pattern_loading(&self) -> bool {
+        self.is_pattern_data_loading
+    }
+
+    /// Returns true while a `C50 02` driver configuration load session is open.
+    pub fn is_driver_loading(&self) -> bool {
+        self.is_driver_data_loading
+    }
+
+    /// Returns how many pattern addresses a `C50 00` session can load. Loading
+    /// starts at address 1, so this is one less than the memory size.
+    pub fn pattern_capacity(&self) -> u32 {
+        self.fpgas[0].pattern_memory_a.len() as u32 - 1
+    }
+
+    /// Simulator-only: chooses the bank the next `C50 00` session loads into.
+    ///
+    /// The host protocol has no bank-select command, so a real host always
+    /// loads bank A. This lets tests and tools fill bank B through the normal
+    /// `P`/`R` frames. A session already open keeps its bank.
+    pub fn select_load_bank(&mut self, bank: MemoryBank) {
+        self.next_load_bank = bank;
+    }
+
+    /// Returns the pattern addresses written by the last completed load of
+    /// `bank`. Loading starts at address 1, so the range is empty until data arrives.
+    pub fn loaded_pattern_range(&self, bank: MemoryBank) -> std::ops::Range<u32> {
+        1..self.pattern_end[bank as usize].max(1)
+    }
+
+    /// Returns true if a second FPGA is fitted. This selects the two-FPGA layout
+    /// of the binary 'P' and 'R' pattern frames.
+    pub fn has_dual_fpga(&self) -> bool {
+        self.fpgas[1].present
+    }
+
     /// Helper to update the driver checksum and log the change.
     fn update_driver_checksum(&mut self, value_to_add: u32) {
         self.driver_data_checksum = self.driver_data_checksum.wrapping_add(value_to_add);
@@ -477,7 +1106,7 @@ impl Simulator {
     /// This is only used for 'C' commands which are known to be ASCII.
     fn parse_command(&self, content: &str) -> Result<Command, CommandError> {
         let cmd_id_str = &content[3..5];
-        let cmd_id = u8::from_str_radix(cmd_id_str, 10).map_err(CommandError::InvalidCommandId)?;
+        let cmd_id = cmd_id_str.parse::<u8>().map_err(CommandError::InvalidCommandId)?;
 
         match cmd_id {
             1 => Ok(Command::ClearClockFail),
@@ -534,7 +1163,7 @@ impl Simulator {
                     return Err(CommandError::TooShort);
                 }
                 let param_str = &content[5..7];
-                let param = u8::from_str_radix(param_str, 10).map_err(|_| CommandError::InvalidParameter)?;
+                let param = param_str.parse::<u8>().map_err(|_| CommandError::InvalidParameter)?;
                 match param {
                     0 => Ok(Command::DataLoad(DataLoadMode::StartPatternLoad)),
                     1 => Ok(Command::DataLoad(DataLoadMode::EndPatternLoad)),
@@ -641,19 +1270,14 @@ impl Simulator {
                 continue;
             }
 
-            // CRITICAL FIX: Simulate the hardware scaling.
-            // Convert the 12-bit DAC value (0-4095) from the voltage_setpoint
-            // into a simulated 0-10V ADC reading.
-            let raw_voltage_reading = psu.voltage_setpoint as f32 / 409.5;
+            // Simulate the hardware scaling and calibration of the voltage_setpoint.
+            let final_voltage = psu.output_voltage(psu.voltage_setpoint);
 
             // Simulate a small current draw. We'll model the raw ADC reading for current
             // as being 5% of its 10V range.
             let raw_current_reading = 10.0 * 0.05;
 
-            // Apply the calibration and offset to the correctly scaled ADC readings.
-            let mut final_voltage = raw_voltage_reading * psu.psu_cal_val;
-            final_voltage += psu.v_cal_offset_val;
-
+            // Apply the calibration and offset to the scaled current reading.
             let mut final_current = raw_current_reading + psu.i_cal_offset_val;
             final_current *= psu.i_cal_val;
 
@@ -661,6 +1285,10 @@ impl Simulator {
             psu.measured_voltage = if final_voltage < 0.0 { 0.0 } else { final_voltage };
             psu.measured_current = if final_current < 0.0 { 0.0 } else { final_current };
         }
+
+        for sw in self.sine_waves.iter_mut() {
+            sw.rms_value = sw.simulated_rms();
+        }
     }
 
     /// Executes a parsed command and returns the response string.
@@ -673,6 +1301,7 @@ impl Simulator {
                 for gen in self.clock_generators.iter_mut() {
                     gen.has_failure = false;
                 }
+                self.clock_status_latch = 0;
                 String::from("#OK#")
             }
             Command::ClearSwFail => {
@@ -688,24 +1317,16 @@ impl Simulator {
                 self.system_config.auto_reset_counter = 0;
                 self.system_config.ignore_clock_fails = false;
 
-                // ADDED: This is the essential logic that enables the PSUs.
-                // It mimics the behavior of the C firmware's Sequence_ON function.
-                for psu in self.psus.iter_mut() {
-                    // A PSU is considered active if its final step voltage (loaded by a 'V' command) is non-zero.
-                    if psu.voltage_set_s4 > 0 {
-                        psu.enabled = true;
-                        // Apply the final step voltage as the current setpoint.
-                        psu.voltage_setpoint = psu.voltage_set_s4 as f32;
-                    } else {
-                        psu.enabled = false;
-                        psu.voltage_setpoint = 0.0;
-                    }
-                }
-
-                self.sequence_on = true;
+                self.clock.cancel(|e| *e == SimEvent::AutoReset);
+                self.start_sequence();
+                // Anything due straight away is applied before the response.
+                self.advance(Duration::ZERO);
                 String::from("#ON#")
             }
             Command::SequenceOff => {
+                self.clock.cancel(|e| *e == SimEvent::AutoReset);
+                self.stop_ptc();
+                self.power_down();
                 self.sequence_on = false;
                 String::from("#OFF#")
             }
@@ -725,9 +1346,12 @@ impl Simulator {
                     _ => [0; 6],
                 };
 
-                for i in 0..6 {
-                    self.psus[i].enabled = true;
-                    self.psus[i].voltage_setpoint = setpoints[i] as f32;
+                self.clock.cancel(|e| *e == SimEvent::AutoReset);
+                self.stop_ptc();
+                self.cancel_power_events();
+                for (psu, setpoint) in self.psus.iter_mut().zip(setpoints) {
+                    psu.enabled = true;
+                    psu.voltage_setpoint = setpoint as f32;
                 }
 
                 self.sequence_on = true;
@@ -743,14 +1367,11 @@ impl Simulator {
                     self.amon_test_count = 0;
                     self.amon_tests.iter_mut().for_each(|t| *t = AmonTest::default());
 
-                    if self.fpgas[0].present {
-                        self.fpgas[0].pattern_memory_a.fill(0);
-                        self.fpgas[0].pattern_memory_b.fill(0);
-                        self.fpgas[0].tristate_memory_a.fill(0);
-                    }
-                    if self.fpgas[1].present {
-                        self.fpgas[1].tristate_memory_b.fill(0);
+                    for fpga in self.fpgas.iter_mut().filter(|fpga| fpga.present) {
+                        fpga.clear_memories();
                     }
+                    self.pattern_end = [1; 2];
+                    self.active_bank = MemoryBank::A;
                 }
                 String::from("#OK#")
             }
@@ -798,11 +1419,18 @@ impl Simulator {
                 DataLoadMode::StartPatternLoad => {
                     self.is_pattern_data_loading = true;
                     self.is_driver_data_loading = false;
+                    self.load_bank = self.next_load_bank;
                     self.sram_address = 1;
                     self.pattern_data_checksum = 0;
                     String::from("#OK#")
                 }
                 DataLoadMode::EndPatternLoad => {
+                    // A completed load becomes the running program, so a host can
+                    // load one bank while the other keeps playing.
+                    if self.is_pattern_data_loading {
+                        self.pattern_end[self.load_bank as usize] = self.sram_address;
+                        self.active_bank = self.load_bank;
+                    }
                     self.is_pattern_data_loading = false;
                     format!("#{},{},#", self.pattern_data_checksum, self.sram_address)
                 }
@@ -840,7 +1468,7 @@ impl Simulator {
             self.alarm_values[1] + 1000,
             self.alarm_values[2] + 1000,
             self.alarm_values[3] + 1000,
-            if self.door_open { 0 } else { 1 } // C code: 0=Open, 1=Close
+            if self.is_door_open() { 0 } else { 1 } // C code: 0=Open, 1=Close
         )
     }
 
@@ -929,36 +1557,38 @@ impl Simulator {
         response.push_str(&format!("{},", self.system_config.auto_reset_counter + 1000));
 
         // PSU Fault Status (3 parts: OverCurrent, UnderVoltage, OverVoltage)
-        // CHANGED: This logic now correctly checks measured values against limits.
+        // Measured values are checked against the limits, and injected faults are added in.
+        let (over_current, under_voltage, over_voltage) = self.psu_fault_flags();
         let mut fault_flags = String::new();
-        for psu in &self.psus { fault_flags.push(if psu.measured_current > psu.current_monitor_limit {'1'} else {'0'}); }
-        for psu in &self.psus { fault_flags.push(if psu.measured_voltage < psu.low_voltage_limit {'1'} else {'0'}); }
-        for psu in &self.psus { fault_flags.push(if psu.measured_voltage > psu.high_voltage_limit {'1'} else {'0'}); }
+        for flags in [over_current, under_voltage, over_voltage] {
+            for i in 0..self.psus.len() { fault_flags.push(if (flags >> i) & 1 == 1 {'1'} else {'0'}); }
+        }
         response.push_str(&fault_flags);
 
-        // Clock Status (placeholder values for now)
-        let clock_status_1_32 = 0u32;
-        let clock_status_33_64 = 0u32;
+        // Clock Status
+        let clock_status = self.clock_status();
+        let clock_status_1_32 = clock_status as u32;
+        let clock_status_33_64 = (clock_status >> 32) as u32;
         response.push_str(&format!(",{:X},", (clock_status_1_32 >> 16) + 0x10000));
         response.push_str(&format!("{:X},", (clock_status_1_32 & 0xFFFF) + 0x10000));
         response.push_str(&format!("{:X},", (clock_status_33_64 >> 16) + 0x10000));
         response.push_str(&format!("{:X},", (clock_status_33_64 & 0xFFFF) + 0x10000));
 
         // Sine Wave Status
-        let sw_status = (if self.sine_waves[0].has_failure {1} else {0}) + (if self.sine_waves[1].has_failure {2} else {0});
+        let sw_status = self.sw_status();
         response.push_str(&format!("{:X},", sw_status + 0x100));
         response.push_str(&format!("{:.2},", self.sine_waves[0].rms_value + 100.0));
         response.push_str(&format!("{:.2},", self.sine_waves[1].rms_value + 100.0));
 
-        // Driver Status
-        response.push_str(&format!("{},", if self.sequence_on { 1 } else { 0 }));
+        // Driver Status (off during a PTC off period)
+        response.push_str(&format!("{},", if self.sequence_on && !self.ptc_off_phase { 1 } else { 0 }));
 
         // Timers and Alarms
         for val in &self.timer_values { response.push_str(&format!("{},", val + 1000)); }
         for val in &self.alarm_values { response.push_str(&format!("{},", val + 1000)); }
 
         // Door Status (last item, no trailing comma)
-        response.push_str(&format!("{}", if self.door_open { 0 } else { 1 }));
+        response.push_str(&format!("{}", if self.is_door_open() { 0 } else { 1 }));
 
         response.push('#');
         response
@@ -1035,7 +1665,7 @@ impl Simulator {
     /// Returns a tuple of (measured_value, pass_fail_status).
     fn measure_amon_test_data(&self, test_index: usize) -> (f32, u32) {
         let test = &self.amon_tests[test_index];
-        let mut measured_value = 0.0;
+        let mut measured_value;
 
         // Since we don't have a real ADC, we'll simulate a reading.
         // A simple approach is to generate a value that would pass the test.
@@ -1431,6 +2061,9 @@ impl Simulator {
         self.alarm_values[1] = sram6;
         self.alarm_values[2] = sram7;
         self.alarm_values[3] = sram8;
+        // Freshly loaded timers start a new countdown.
+        self.alarms_raised = [false; 4];
+        self.timer_carry = Duration::ZERO;
 
         self.update_driver_checksum(sram1 + sram2 + sram3 + sram4 + sram5 + sram6 + sram7 + sram8);
         Ok(())
@@ -1458,7 +2091,7 @@ impl Simulator {
             if psu.pos_neg_i == 1 {
                 psu.i_cal_offset_val *= -1.0;
             }
-        } else if sram3_psu_num >= 7 && sram3_psu_num < 9 {
+        } else if (7..9).contains(&sram3_psu_num) {
             // Special case for voltage offset config
             let target_psu_index = sram3_psu_num - 7; // 7 -> 0, 8 -> 1
             let psu = &mut self.psus[target_psu_index];
@@ -1770,13 +2403,24 @@ impl Simulator {
         Ok(())
     }
 
+    /// Returns an overflow error unless `words` more addresses fit from `sram_address`.
+    /// A frame that does not fit is dropped whole, rather than wrapping round to
+    /// overwrite the start of the pattern.
+    fn check_pattern_space(&self, words: usize) -> Result<(), CommandError> {
+        if self.sram_address as usize + words > self.fpgas[0].pattern_memory_a.len() {
+            return Err(CommandError::PatternMemoryOverflow { capacity: self.pattern_capacity() });
+        }
+        Ok(())
+    }
+
     /// Parses a 'P' command, updates FPGA memory, and updates the checksum.
     fn handle_p_command(&mut self, content_bytes: &[u8]) -> Result<(), CommandError> {
         let bytes = content_bytes;
         let mut checksum_update: u32 = 0;
 
-        if self.fpgas[1].present { // Two FPGAs
+        if self.has_dual_fpga() { // Two FPGAs
             if bytes.len() < 19 { return Err(CommandError::TooShort); }
+            self.check_pattern_space(2)?;
             let sram1 = u32::from_le_bytes(bytes[1..5].try_into().unwrap());
             let sram2 = u32::from_le_bytes(bytes[5..9].try_into().unwrap());
             let sram3 = bytes[9] as u32;
@@ -1784,11 +2428,11 @@ impl Simulator {
             let sram5 = u32::from_le_bytes(bytes[14..18].try_into().unwrap());
             let sram6 = bytes[18] as u32;
 
-            self.fpgas[0].pattern_memory_a[self.sram_address as usize] = sram1;
-            self.fpgas[1].pattern_memory_a[self.sram_address as usize] = sram2;
+            self.fpgas[0].pattern_memory_mut(self.load_bank)[self.sram_address as usize] = sram1;
+            self.fpgas[1].pattern_memory_mut(self.load_bank)[self.sram_address as usize] = sram2;
             self.sram_address += 1;
-            self.fpgas[0].pattern_memory_a[self.sram_address as usize] = sram4;
-            self.fpgas[1].pattern_memory_a[self.sram_address as usize] = sram5;
+            self.fpgas[0].pattern_memory_mut(self.load_bank)[self.sram_address as usize] = sram4;
+            self.fpgas[1].pattern_memory_mut(self.load_bank)[self.sram_address as usize] = sram5;
             self.sram_address += 1;
 
             checksum_update += sram3 + sram6;
@@ -1796,6 +2440,7 @@ impl Simulator {
             for &byte in &bytes[10..18] { checksum_update += byte as u32; }
         } else { // One FPGA
             if bytes.len() < 21 { return Err(CommandError::TooShort); }
+            self.check_pattern_space(4)?;
             let sram1 = u32::from_le_bytes(bytes[1..5].try_into().unwrap());
             let sram2 = bytes[5] as u32;
             let sram3 = u32::from_le_bytes(bytes[6..10].try_into().unwrap());
@@ -1805,10 +2450,10 @@ impl Simulator {
             let sram7 = u32::from_le_bytes(bytes[16..20].try_into().unwrap());
             let sram8 = bytes[20] as u32;
 
-            self.fpgas[0].pattern_memory_a[self.sram_address as usize] = sram1; self.sram_address += 1;
-            self.fpgas[0].pattern_memory_a[self.sram_address as usize] = sram3; self.sram_address += 1;
-            self.fpgas[0].pattern_memory_a[self.sram_address as usize] = sram5; self.sram_address += 1;
-            self.fpgas[0].pattern_memory_a[self.sram_address as usize] = sram7; self.sram_address += 1;
+            self.fpgas[0].pattern_memory_mut(self.load_bank)[self.sram_address as usize] = sram1; self.sram_address += 1;
+            self.fpgas[0].pattern_memory_mut(self.load_bank)[self.sram_address as usize] = sram3; self.sram_address += 1;
+            self.fpgas[0].pattern_memory_mut(self.load_bank)[self.sram_address as usize] = sram5; self.sram_address += 1;
+            self.fpgas[0].pattern_memory_mut(self.load_bank)[self.sram_address as usize] = sram7; self.sram_address += 1;
 
             checksum_update += sram2 + sram4 + sram6 + sram8;
             for &byte in &bytes[1..5] { checksum_update += byte as u32; }
@@ -1826,8 +2471,9 @@ impl Simulator {
         let bytes = content_bytes;
         let mut checksum_update: u32 = 0;
 
-        if self.fpgas[1].present { // Two FPGAs
+        if self.has_dual_fpga() { // Two FPGAs
             if bytes.len() < 19 { return Err(CommandError::TooShort); }
+            self.check_pattern_space(2)?;
             let sram1 = u32::from_le_bytes(bytes[1..5].try_into().unwrap());
             let sram2 = u32::from_le_bytes(bytes[5..9].try_into().unwrap());
             let sram3 = bytes[9] as u32;
@@ -1836,11 +2482,11 @@ impl Simulator {
             let sram6 = bytes[18] as u32;
 
             // Note the bitwise NOT, as seen in the C code.
-            self.fpgas[0].tristate_memory_a[self.sram_address as usize] = !sram1;
-            self.fpgas[1].tristate_memory_a[self.sram_address as usize] = !sram2;
+            self.fpgas[0].tristate_memory_mut(self.load_bank)[self.sram_address as usize] = !sram1;
+            self.fpgas[1].tristate_memory_mut(self.load_bank)[self.sram_address as usize] = !sram2;
             self.sram_address += 1;
-            self.fpgas[0].tristate_memory_a[self.sram_address as usize] = !sram4;
-            self.fpgas[1].tristate_memory_a[self.sram_address as usize] = !sram5;
+            self.fpgas[0].tristate_memory_mut(self.load_bank)[self.sram_address as usize] = !sram4;
+            self.fpgas[1].tristate_memory_mut(self.load_bank)[self.sram_address as usize] = !sram5;
             self.sram_address += 1;
 
             checksum_update += sram3 + sram6;
@@ -1848,6 +2494,7 @@ impl Simulator {
             for &byte in &bytes[10..18] { checksum_update += byte as u32; }
         } else { // One FPGA
             if bytes.len() < 21 { return Err(CommandError::TooShort); }
+            self.check_pattern_space(4)?;
             let sram1 = u32::from_le_bytes(bytes[1..5].try_into().unwrap());
             let sram2 = bytes[5] as u32;
             let sram3 = u32::from_le_bytes(bytes[6..10].try_into().unwrap());
@@ -1858,10 +2505,10 @@ impl Simulator {
             let sram8 = bytes[20] as u32;
 
             // Note the bitwise NOT.
-            self.fpgas[0].tristate_memory_a[self.sram_address as usize] = !sram1; self.sram_address += 1;
-            self.fpgas[0].tristate_memory_a[self.sram_address as usize] = !sram3; self.sram_address += 1;
-            self.fpgas[0].tristate_memory_a[self.sram_address as usize] = !sram5; self.sram_address += 1;
-            self.fpgas[0].tristate_memory_a[self.sram_address as usize] = !sram7; self.sram_address += 1;
+            self.fpgas[0].tristate_memory_mut(self.load_bank)[self.sram_address as usize] = !sram1; self.sram_address += 1;
+            self.fpgas[0].tristate_memory_mut(self.load_bank)[self.sram_address as usize] = !sram3; self.sram_address += 1;
+            self.fpgas[0].tristate_memory_mut(self.load_bank)[self.sram_address as usize] = !sram5; self.sram_address += 1;
+            self.fpgas[0].tristate_memory_mut(self.load_bank)[self.sram_address as usize] = !sram7; self.sram_address += 1;
 
             checksum_update += sram2 + sram4 + sram6 + sram8;
             for &byte in &bytes[1..5] { checksum_update += byte as u32; }
@@ -1876,6 +2523,7 @@ impl Simulator {
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison, clippy::identity_op)]
 mod tests {
     use super::*;
 
@@ -2037,6 +2685,7 @@ mod tests {
         let mut sim = Simulator::new(0x1F);
         sim.fpgas[0].present = true;
         sim.fpgas[0].pattern_memory_a[10] = 0xDEADBEEF; // Pre-fill some data
+        sim.fpgas[0].tristate_memory_b[10] = 0xFEEDF00D;
         sim.system_config.clocks_required = true;
         sim.amon_test_count = 5;
 
@@ -2059,6 +2708,7 @@ mod tests {
         assert_eq!(sim.prog_id_lint, 0);
         // Verify state IS cleared
         assert_eq!(sim.fpgas[0].pattern_memory_a[10], 0);
+        assert_eq!(sim.fpgas[0].tristate_memory_b[10], 0);
         assert_eq!(sim.system_config.clocks_required, false);
         assert_eq!(sim.amon_test_count, 0);
     }
@@ -2228,8 +2878,16 @@ mod tests {
         sim.psus[5].high_voltage_limit = 900.0; // 900.5 > 900.0 -> Over-voltage
         sim.psus[5].current_monitor_limit = 6.0; // 6.78 > 6.0 -> Over-current
 
-        sim.sine_waves[0].rms_value = 1.11;
-        sim.sine_waves[1].rms_value = 2.22;
+        // SW1 outputs a 2V peak sine (1.41V RMS) and SW2 a 4V DC offset.
+        for sw in sim.sine_waves.iter_mut() {
+            sw.present = true;
+            sw.programmed = true;
+            sw.enabled = true;
+            sw.duty_cycle = 100;
+        }
+        sim.sine_waves[0].amplitude = 819;
+        sim.sine_waves[0].frequency_base = 1;
+        sim.sine_waves[1].offset = 1638;
         sim.sequence_on = true;
         sim.door_open = false;
 
@@ -2237,7 +2895,7 @@ mod tests {
 
         // FIXED: The expected string is updated to reflect the correct simulated
         // measured values and the resulting fault flags.
-        let expected_vi = "#100.00,100.50,100.00,100.50,100.00,100.50,100.00,100.50,100.00,100.50,102.20,100.50,1000,000000000000000000,10000,10000,10000,10000,100,101.11,102.22,1,1000,1000,1000,1000,1000,1000,1000,1000,1#";
+        let expected_vi = "#100.00,100.50,100.00,100.50,100.00,100.50,100.00,100.50,100.00,100.50,102.20,100.50,1000,000000000000000000,10000,10000,10000,10000,100,101.41,104.00,1,1000,1000,1000,1000,1000,1000,1000,1000,1#";
         assert_eq!(result.response, Some(expected_vi.to_string()));
     }
 
@@ -3028,4 +3686,563 @@ mod tests {
         let end_result = sim.process_command(b"<C1F5003>").unwrap();
         assert_eq!(end_result.response, Some(format!("#{}#", expected_checksum)));
     }
+
+    #[test]
+    fn advance_runs_monitor_cycle_on_simulated_time() {
+        let mut sim = Simulator::new(0x1F);
+        sim.psus[0].voltage_setpoint = 4095.0;
+
+        sim.advance(Duration::from_millis(99));
+        assert_eq!(sim.now(), Duration::from_millis(99));
+        assert_eq!(sim.psus[0].measured_voltage, 0.0);
+
+        // The first monitor pass is due at 100 ms.
+        sim.advance(Duration::from_millis(1));
+        assert_eq!(sim.psus[0].measured_voltage, 10.0);
+
+        sim.psus[0].voltage_setpoint = 2047.5;
+        sim.advance(Duration::from_secs(10));
+        assert_eq!(sim.now(), Duration::from_millis(10_100));
+        assert_eq!(sim.psus[0].measured_voltage, 5.0);
+    }
+
+    /// Returns the (voltage, current) fields of PSU `n` (0-based) from a `C24` response.
+    fn vi_psu_reading(response: &str, n: usize) -> (f32, f32) {
+        let fields: Vec<&str> = response.trim_matches('#').split(',').collect();
+        (fields[2 * n].parse().unwrap(), fields[2 * n + 1].parse().unwrap())
+    }
+
+    #[test]
+    fn sequence_on_steps_psus_in_sequence_order() {
+        let mut sim = Simulator::new(0x1F);
+        sim.system_config.psu_sequence_enabled = true;
+        sim.system_config.psu_step_enabled = true;
+        sim.system_config.psu_step_delay = 100;
+        for (i, psu) in sim.psus.iter_mut().take(2).enumerate() {
+            psu.voltage_set_s1 = 409 + i as u16;
+            psu.voltage_set_s2 = 819;
+            psu.voltage_set_s3 = 1228;
+            psu.voltage_set_s4 = 4095;
+        }
+        // PSU 2 comes up first, 50 ms after C03; PSU 1 follows 20 ms later.
+        sim.psus[0].sequence_id = 2;
+        sim.psus[0].sequence_delay = 20;
+        sim.psus[1].sequence_id = 1;
+        sim.psus[1].sequence_delay = 50;
+
+        sim.process_command(b"<C1F03>").unwrap();
+        assert!(sim.sequence_on);
+        assert!(!sim.psus[0].enabled);
+        assert!(!sim.psus[1].enabled);
+
+        sim.advance(Duration::from_millis(50));
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        assert_eq!(vi_psu_reading(&vi, 0).0, 100.0);
+        assert_eq!(vi_psu_reading(&vi, 1).0, 101.0);
+        assert!(!sim.psus[0].enabled);
+        assert!(sim.psus[1].enabled);
+
+        sim.advance(Duration::from_millis(20));
+        assert!(sim.psus[0].enabled);
+        assert_eq!(sim.psus[0].voltage_setpoint, 409.0);
+
+        // Step 2 starts psu_step_delay later: PSU 2 at 220 ms, PSU 1 at 240 ms.
+        sim.advance(Duration::from_millis(150));
+        assert_eq!(sim.psus[1].voltage_setpoint, 819.0);
+        assert_eq!(sim.psus[0].voltage_setpoint, 409.0);
+
+        // Step 4 completes at 580 ms.
+        sim.advance(Duration::from_millis(359));
+        assert_eq!(sim.psus[0].voltage_setpoint, 1228.0);
+        sim.advance(Duration::from_millis(1));
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        assert_eq!(vi_psu_reading(&vi, 0).0, 110.0);
+        assert_eq!(vi_psu_reading(&vi, 1).0, 110.0);
+
+        // As in the firmware, PSUs with no step voltages are still switched on, at 0 V.
+        assert!(sim.psus[2].enabled);
+        assert_eq!(sim.psus[2].voltage_setpoint, 0.0);
+    }
+
+    #[test]
+    fn sequence_off_abandons_power_up() {
+        let mut sim = Simulator::new(0x1F);
+        sim.system_config.psu_sequence_enabled = true;
+        sim.psus[0].voltage_set_s4 = 4095;
+        sim.psus[1].voltage_set_s4 = 4095;
+        sim.psus[1].sequence_id = 1;
+        sim.psus[1].sequence_delay = 500;
+
+        // PSU 1 has no delay, so it is on as soon as C03 returns.
+        sim.process_command(b"<C1F03>").unwrap();
+        assert_eq!(sim.psus[0].voltage_setpoint, 4095.0);
+        assert!(!sim.psus[1].enabled);
+
+        sim.process_command(b"<C1F04>").unwrap();
+        sim.advance(Duration::from_secs(1));
+        assert!(!sim.sequence_on);
+        assert!(sim.psus.iter().all(|psu| !psu.enabled && psu.voltage_setpoint == 0.0));
+    }
+
+    #[test]
+    fn micro_stepping_ramps_setpoints_on_and_off() {
+        let mut sim = Simulator::new(0x1F);
+        sim.process_command(b"<C1F5002>").unwrap();
+        // PSU 1: 4 micro-steps, 10 ms apart.
+        sim.process_command(b"<Mxx010041000A0000000>").unwrap();
+        sim.process_command(b"<C1F5003>").unwrap();
+        sim.psus[0].voltage_set_s4 = 4000;
+
+        sim.process_command(b"<C1F03>").unwrap();
+        assert!(sim.psus[0].enabled);
+        assert_eq!(sim.psus[0].voltage_setpoint, 0.0);
+
+        sim.advance(Duration::from_millis(10));
+        assert_eq!(sim.psus[0].voltage_setpoint, 1000.0);
+        sim.advance(Duration::from_millis(10));
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        assert_eq!(vi_psu_reading(&vi, 0).0, 104.88);
+        sim.advance(Duration::from_millis(20));
+        assert_eq!(sim.psus[0].voltage_setpoint, 4000.0);
+
+        // Sequence off ramps back down before the PSU is switched off.
+        sim.process_command(b"<C1F04>").unwrap();
+        sim.advance(Duration::from_millis(10));
+        assert_eq!(sim.psus[0].voltage_setpoint, 3000.0);
+        assert!(sim.psus[0].enabled);
+        sim.advance(Duration::from_millis(30));
+        assert_eq!(sim.psus[0].voltage_setpoint, 0.0);
+        assert!(!sim.psus[0].enabled);
+    }
+
+    #[test]
+    fn ptc_cycles_power_on_the_loaded_schedule() {
+        let mut sim = Simulator::new(0x1F);
+        sim.process_command(b"<C1F5002>").unwrap();
+        // PTC enabled: 2 s on, 3 s off, times in seconds.
+        sim.process_command(b"<Zxx010002000301>").unwrap();
+        sim.process_command(b"<C1F5003>").unwrap();
+        sim.psus[0].voltage_set_s4 = 4095;
+
+        let driver_status = |sim: &mut Simulator| {
+            let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+            vi.trim_matches('#').split(',').nth(21).unwrap().to_string()
+        };
+
+        sim.process_command(b"<C1F03>").unwrap();
+        assert_eq!(driver_status(&mut sim), "1");
+        assert_eq!(vi_psu_reading(&sim.process_command(b"<C1F24>").unwrap().response.unwrap(), 0).0, 110.0);
+
+        sim.advance(Duration::from_secs(2));
+        assert_eq!(driver_status(&mut sim), "0");
+        assert!(sim.ptc_off_phase);
+        assert_eq!(vi_psu_reading(&sim.process_command(b"<C1F24>").unwrap().response.unwrap(), 0).0, 100.0);
+        assert!(sim.sequence_on);
+
+        sim.advance(Duration::from_secs(3));
+        assert_eq!(driver_status(&mut sim), "1");
+        assert_eq!(sim.psus[0].voltage_setpoint, 4095.0);
+
+        // Sequence off ends the cycle for good.
+        sim.process_command(b"<C1F04>").unwrap();
+        sim.advance(Duration::from_secs(60));
+        assert_eq!(driver_status(&mut sim), "0");
+        assert!(!sim.ptc_off_phase);
+        assert!(!sim.psus[0].enabled);
+    }
+
+    #[test]
+    fn timers_count_down_only_while_temp_ok_and_sequence_on() {
+        let mut sim = Simulator::new(0x1F);
+        sim.process_command(b"<C1F5002>").unwrap();
+        // Alarms 0, 0, 1, 3; timers 4, 3, 2, 5 (minutes).
+        sim.process_command(b"<Txx0301000005020304>").unwrap();
+        sim.process_command(b"<C1F5003>").unwrap();
+
+        sim.process_command(b"<C1F03>").unwrap();
+        sim.advance(Duration::from_secs(120));
+        assert_eq!(sim.timer_values, [4, 3, 2, 5]);
+
+        sim.process_command(b"<C1F1600000000000001>").unwrap();
+        sim.advance(Duration::from_secs(90));
+        assert_eq!(sim.timer_values, [3, 2, 1, 4]);
+        assert_eq!(sim.alarms_raised, [false, false, true, false]);
+
+        // The half minute already counted carries over.
+        sim.advance(Duration::from_secs(30));
+        let reference = sim.process_command(b"<C1F17>").unwrap().response.unwrap();
+        assert!(reference.contains(",1002,1001,1000,1003,"));
+        assert_eq!(sim.alarms_raised, [false, false, true, true]);
+
+        sim.process_command(b"<C1F04>").unwrap();
+        sim.advance(Duration::from_secs(600));
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        assert!(vi.ends_with(",1002,1001,1000,1003,1000,1000,1001,1003,1#"));
+        assert_eq!(sim.alarms_raised, [false, false, true, true]);
+    }
+
+    #[test]
+    fn timer_unit_is_configurable() {
+        let mut sim = Simulator::new(0x1F);
+        sim.timer_unit = Duration::from_secs(1);
+        sim.process_command(b"<C1F5002>").unwrap();
+        // Alarm 1 at 2 counts; timer 1 at 5.
+        sim.process_command(b"<Txx0000000200000005>").unwrap();
+        sim.process_command(b"<C1F5003>").unwrap();
+        sim.process_command(b"<C1F1600000000000001>").unwrap();
+        sim.process_command(b"<C1F03>").unwrap();
+
+        sim.advance(Duration::from_secs(3));
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        assert!(vi.ends_with(",1002,1000,1000,1000,1002,1000,1000,1000,1#"), "{}", vi);
+        assert_eq!(sim.alarms_raised, [true, false, false, false]);
+    }
+
+    #[test]
+    fn injected_faults_appear_in_vi_monitor_string() {
+        let mut sim = Simulator::new(0x1F);
+        sim.inject_fault(Fault::OverCurrent(0)).unwrap();
+        sim.inject_fault(Fault::UnderVoltage(2)).unwrap();
+        sim.inject_fault(Fault::OverVoltage(5)).unwrap();
+        sim.inject_fault(Fault::ClockFailure(0)).unwrap();
+        sim.inject_fault(Fault::ClockFailure(17)).unwrap();
+        sim.inject_fault(Fault::ClockFailure(63)).unwrap();
+        sim.inject_fault(Fault::SineWaveFailure(1)).unwrap();
+        sim.inject_fault(Fault::DoorOpen).unwrap();
+        assert_eq!(sim.inject_fault(Fault::OverCurrent(6)), Err(CommandError::InvalidParameter));
+
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        let fields: Vec<&str> = vi.trim_matches('#').split(',').collect();
+        assert_eq!(fields[13], "100000001000000001");
+        assert_eq!(&fields[14..18], ["10002", "10001", "18000", "10000"]);
+        assert_eq!(fields[18], "102");
+        assert_eq!(fields[30], "0");
+
+        sim.clear_fault(Fault::DoorOpen).unwrap();
+        sim.clear_fault(Fault::ClockFailure(63)).unwrap();
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        let fields: Vec<&str> = vi.trim_matches('#').split(',').collect();
+        assert_eq!(fields[16], "10000");
+        assert_eq!(fields[30], "1");
+
+        sim.clear_all_faults();
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        assert!(vi.contains(",000000000000000000,10000,10000,10000,10000,100,"));
+    }
+
+    #[test]
+    fn injected_temperature_fault_stops_timers() {
+        let mut sim = Simulator::new(0x1F);
+        sim.timer_values = [5; 4];
+        sim.process_command(b"<C1F1600000000000001>").unwrap();
+        sim.process_command(b"<C1F03>").unwrap();
+        sim.inject_fault(Fault::TempNotOk).unwrap();
+
+        sim.advance(Duration::from_secs(300));
+        assert_eq!(sim.timer_values, [5; 4]);
+        sim.clear_fault(Fault::TempNotOk).unwrap();
+        sim.advance(Duration::from_secs(60));
+        assert_eq!(sim.timer_values, [4; 4]);
+    }
+
+    #[test]
+    fn tripped_faults_are_captured_into_rotating_history() {
+        let mut sim = Simulator::new(0x1F);
+        sim.psus[0].voltage_set_s4 = 409;
+        sim.psus[0].high_voltage_limit = 5.0;
+        sim.timer_values = [7, 0, 0, 0];
+        sim.process_command(b"<C1F1600000000000001>").unwrap();
+        sim.process_command(b"<C1F03>").unwrap();
+        sim.advance(Duration::from_millis(100));
+        assert_eq!(sim.fault_log_index, 0);
+
+        sim.inject_fault(Fault::ClockFailure(16)).unwrap();
+        sim.advance(Duration::from_millis(100));
+        assert_eq!(sim.fault_log_index, 1);
+        let log = &sim.fault_logs[0];
+        assert_eq!(log.clock_status_17_32, 1);
+        assert!(log.driver_on);
+        assert_eq!(log.timer_values, [7, 0, 0, 0]);
+
+        // A fault that stays present is only logged once.
+        sim.advance(Duration::from_secs(1));
+        assert_eq!(sim.fault_log_index, 1);
+
+        let fault_string = sim.process_command(b"<C1F2000000000000000>").unwrap().response.unwrap();
+        assert_eq!(
+            fault_string,
+            "#101.00,100.50,100.00,100.50,100.00,100.50,100.00,100.50,100.00,100.50,100.00,100.50,1000,000000000000000000,10001,10000,10000,10000,100,100.00,100.00,1,1007,1000,1000,1000,1000,1000,1000,1000#"
+        );
+
+        // Eleven more faults wrap around the ten slots.
+        for n in 0..11 {
+            sim.inject_fault(Fault::OverVoltage(n % 6)).unwrap();
+            sim.advance(Duration::from_millis(100));
+            sim.clear_fault(Fault::OverVoltage(n % 6)).unwrap();
+            sim.advance(Duration::from_millis(100));
+        }
+        assert_eq!(sim.fault_log_index, 2);
+        assert_eq!(sim.fault_logs[1].over_voltage_flags, 1 << 4);
+        assert_eq!(sim.fault_logs[0].over_voltage_flags, 1 << 3);
+        assert_eq!(sim.fault_logs[2].over_voltage_flags, 1 << 1);
+    }
+
+    #[test]
+    fn stop_on_error_flags_shut_the_sequence_down() {
+        let mut sim = Simulator::new(0x1F);
+        sim.psus[0].voltage_set_s4 = 4095;
+        sim.psus[0].high_voltage_limit = 11.0;
+        sim.psus[0].low_voltage_limit = 9.0;
+        sim.system_config.stop_on_v_error = true;
+
+        // Current faults are reported and logged but do not stop the sequence.
+        sim.process_command(b"<C1F03>").unwrap();
+        sim.inject_fault(Fault::OverCurrent(1)).unwrap();
+        sim.advance(Duration::from_millis(200));
+        assert!(sim.sequence_on);
+        assert_eq!(sim.fault_log_index, 1);
+
+        // A voltage outside the limits does.
+        sim.psus[0].high_voltage_limit = 9.5;
+        sim.advance(Duration::from_millis(100));
+        assert!(!sim.sequence_on);
+        assert!(!sim.psus[0].enabled);
+        assert_eq!(sim.fault_log_index, 2);
+        assert_eq!(sim.fault_logs[1].over_voltage_flags, 1);
+        assert!(sim.fault_logs[1].driver_on);
+
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        assert_eq!(vi.trim_matches('#').split(',').nth(21), Some("0"));
+    }
+
+    #[test]
+    fn stop_on_clock_and_temperature_errors() {
+        let mut sim = Simulator::new(0x1F);
+        sim.system_config.stop_on_clk_error = true;
+        sim.process_command(b"<C1F03>").unwrap();
+        sim.inject_fault(Fault::ClockFailure(5)).unwrap();
+        sim.advance(Duration::from_millis(100));
+        assert!(!sim.sequence_on);
+
+        let mut sim = Simulator::new(0x1F);
+        sim.system_config.stop_on_temp_error = true;
+        sim.process_command(b"<C1F03>").unwrap();
+        sim.advance(Duration::from_millis(500));
+        assert!(sim.sequence_on);
+        sim.inject_fault(Fault::TempNotOk).unwrap();
+        sim.advance(Duration::from_millis(100));
+        assert!(!sim.sequence_on);
+        assert_eq!(sim.fault_log_index, 1);
+    }
+
+    #[test]
+    fn auto_reset_restarts_until_retries_are_used_up() {
+        let mut sim = Simulator::new(0x1F);
+        sim.psus[0].voltage_set_s4 = 4095;
+        sim.psus[0].high_voltage_limit = 20.0;
+        sim.system_config.stop_on_i_error = true;
+        sim.system_config.auto_reset = true;
+        sim.system_config.auto_reset_retries = 2;
+        sim.inject_fault(Fault::OverCurrent(0)).unwrap();
+
+        sim.process_command(b"<C1F03>").unwrap();
+        sim.advance(Duration::from_millis(100));
+        assert!(!sim.sequence_on);
+        assert_eq!(sim.system_config.auto_reset_counter, 0);
+
+        sim.advance(AUTO_RESET_DELAY);
+        assert!(sim.sequence_on);
+        assert_eq!(sim.psus[0].voltage_setpoint, 4095.0);
+        assert_eq!(sim.system_config.auto_reset_counter, 1);
+
+        // The fault is still there, so the board trips again, restarts once more and gives up.
+        sim.advance(Duration::from_secs(10));
+        assert!(!sim.sequence_on);
+        assert_eq!(sim.system_config.auto_reset_counter, 2);
+        assert_eq!(sim.fault_log_index, 3);
+        assert_eq!(sim.fault_logs[2].auto_reset_counter, 2);
+
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        assert_eq!(vi.trim_matches('#').split(',').nth(12), Some("1002"));
+
+        // C03 starts a fresh run with the counter cleared.
+        sim.clear_fault(Fault::OverCurrent(0)).unwrap();
+        sim.process_command(b"<C1F03>").unwrap();
+        assert_eq!(sim.system_config.auto_reset_counter, 0);
+    }
+
+    #[test]
+    fn sequence_off_cancels_pending_auto_reset() {
+        let mut sim = Simulator::new(0x1F);
+        sim.system_config.stop_on_clk_error = true;
+        sim.system_config.auto_reset = true;
+        sim.system_config.auto_reset_retries = 5;
+        sim.inject_fault(Fault::ClockFailure(0)).unwrap();
+
+        sim.process_command(b"<C1F03>").unwrap();
+        sim.advance(Duration::from_millis(100));
+        sim.process_command(b"<C1F04>").unwrap();
+        sim.advance(Duration::from_secs(10));
+        assert!(!sim.sequence_on);
+        assert_eq!(sim.system_config.auto_reset_counter, 0);
+    }
+
+    /// Returns the four clock status fields of a `C24` response.
+    fn vi_clock_fields(sim: &mut Simulator) -> Vec<String> {
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        vi.trim_matches('#').split(',').skip(14).take(4).map(String::from).collect()
+    }
+
+    #[test]
+    fn clock_status_words_follow_modules_and_filters() {
+        let mut sim = Simulator::new(0x1F);
+        sim.clock_generators[0].present = true;
+        sim.clock_generators[2].present = true;
+        assert_eq!(vi_clock_fields(&mut sim), ["10000", "10000", "10000", "10000"]);
+
+        // Module 3 failing fails channels 33-48.
+        sim.clock_generators[2].has_failure = true;
+        assert_eq!(vi_clock_fields(&mut sim), ["10000", "10000", "10000", "1FFFF"]);
+
+        // Channels 33-40 are masked by the filter.
+        sim.system_config.clk64_mon_filter = 0x00FF;
+        assert_eq!(vi_clock_fields(&mut sim), ["10000", "10000", "10000", "1FF00"]);
+
+        // With clocks required, the missing modules 2 and 4 fail too.
+        sim.system_config.clocks_required = true;
+        assert_eq!(vi_clock_fields(&mut sim), ["1FFFF", "10000", "1FFFF", "1FF00"]);
+
+        sim.system_config.ignore_clock_fails = true;
+        assert_eq!(vi_clock_fields(&mut sim), ["10000", "10000", "10000", "10000"]);
+    }
+
+    #[test]
+    fn clock_failures_latch_until_c01() {
+        let mut sim = Simulator::new(0x1F);
+        sim.inject_fault(Fault::ClockFailure(4)).unwrap();
+        sim.advance(Duration::from_millis(100));
+        sim.clear_fault(Fault::ClockFailure(4)).unwrap();
+        assert_eq!(sim.clock_status(), 1 << 4);
+        assert_eq!(vi_clock_fields(&mut sim), ["10000", "10010", "10000", "10000"]);
+
+        sim.process_command(b"<C1F01>").unwrap();
+        assert_eq!(sim.clock_status(), 0);
+    }
+
+    #[test]
+    fn sine_wave_rms_follows_s_command_parameters() {
+        let mut sim = Simulator::new(0x1F);
+        sim.sine_waves[0].present = true;
+        sim.sine_waves[0].programmed = true;
+        sim.process_command(b"<C1F5002>").unwrap();
+        // SW1 enabled, 50% duty, frequency base 1, 1V offset, 4V peak amplitude.
+        sim.process_command(b"<Sxx011000320119A666>").unwrap();
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        let fields: Vec<&str> = vi.trim_matches('#').split(',').collect();
+        // sqrt(1 + 0.5 * 16 / 2) = 2.24
+        assert_eq!(fields[19], "102.24");
+        assert_eq!(fields[20], "100.00");
+        assert_eq!(fields[18], "100");
+
+        sim.sine_waves[0].enabled = false;
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        assert_eq!(vi.trim_matches('#').split(',').nth(19), Some("100.00"));
+    }
+
+    #[test]
+    fn sine_wave_failures_latch_until_c02() {
+        let mut sim = Simulator::new(0x1F);
+        // SW2 enabled but absent.
+        sim.sine_waves[1].enabled = true;
+        assert_eq!(sim.sw_status(), 0b10);
+        sim.advance(Duration::from_millis(100));
+        assert!(sim.sine_waves[1].has_failure);
+
+        sim.sine_waves[1].enabled = false;
+        sim.inject_fault(Fault::SineWaveFailure(0)).unwrap();
+        sim.advance(Duration::from_millis(100));
+        sim.clear_fault(Fault::SineWaveFailure(0)).unwrap();
+        let vi = sim.process_command(b"<C1F24>").unwrap().response.unwrap();
+        assert_eq!(vi.trim_matches('#').split(',').nth(18), Some("103"));
+
+        sim.process_command(b"<C1F02>").unwrap();
+        assert_eq!(sim.sw_status(), 0);
+    }
+
+    #[test]
+    fn pattern_frames_past_the_end_of_memory_are_rejected() {
+        let mut sim = Simulator::new(0x1F);
+        assert_eq!(sim.pattern_capacity(), 0xFFFFF);
+        sim.process_command(b"<C1F5000>").unwrap();
+        sim.sram_address = 0xFFFFE;
+
+        // Four single-FPGA words do not fit in the last two addresses.
+        let p_command = b"<P\x01\x02\x03\x04\x11\x05\x06\x07\x08\x22\x09\x0A\x0B\x0C\x33\x0D\x0E\x0F\x10\x44>";
+        let overflow = Err(CommandError::PatternMemoryOverflow { capacity: 0xFFFFF });
+        assert_eq!(sim.process_command(p_command), overflow);
+        assert_eq!(sim.process_command(&p_command.map(|b| if b == b'P' { b'R' } else { b })), overflow);
+        assert_eq!(sim.sram_address, 0xFFFFE);
+        assert_eq!(sim.fpgas[0].pattern_memory_a[0xFFFFE], 0);
+        assert_eq!(sim.fpgas[0].tristate_memory_a[0xFFFFE], 0);
+        assert_eq!(sim.process_command(b"<C1F5001>").unwrap().response, Some("#0,1048574,#".to_string()));
+
+        // Two dual-FPGA words fill memory exactly.
+        sim.fpgas[1].present = true;
+        sim.process_command(b"<C1F5000>").unwrap();
+        sim.sram_address = 0xFFFFE;
+        sim.process_command(b"<P\x01\x02\x03\x04\x11\x12\x13\x14\xAA\x05\x06\x07\x08\x15\x16\x17\x18\xBB>").unwrap();
+        assert_eq!(sim.fpgas[1].pattern_memory_a[0xFFFFF], 0x18171615);
+        let result = sim.process_command(b"<P\x01\x02\x03\x04\x11\x12\x13\x14\xAA\x05\x06\x07\x08\x15\x16\x17\x18\xBB>");
+        assert!(matches!(result, Err(CommandError::PatternMemoryOverflow { .. })));
+    }
+
+    #[test]
+    fn bank_b_load_leaves_bank_a_playing_until_complete() {
+        let mut sim = Simulator::new(0x1F);
+        sim.process_command(b"<C1F5000>").unwrap();
+        sim.process_command(b"<P\x01\x00\x00\x00\x00\x02\x00\x00\x00\x00\x03\x00\x00\x00\x00\x04\x00\x00\x00\x00>").unwrap();
+        sim.process_command(b"<C1F5001>").unwrap();
+        assert_eq!(sim.active_bank, MemoryBank::A);
+
+        sim.select_load_bank(MemoryBank::B);
+        sim.process_command(b"<C1F5000>").unwrap();
+        sim.process_command(b"<P\x0A\x00\x00\x00\x00\x0B\x00\x00\x00\x00\x0C\x00\x00\x00\x00\x0D\x00\x00\x00\x00>").unwrap();
+        sim.process_command(b"<R\xFF\xFF\xFF\xFF\x00\xFF\xFF\xFF\xFF\x00\xFF\xFF\xFF\xFF\x00\xFE\xFF\xFF\xFF\x00>").unwrap();
+        // Bank A keeps running while bank B is being loaded.
+        assert_eq!(sim.active_bank, MemoryBank::A);
+        let result = sim.process_command(b"<C1F5001>").unwrap();
+        assert_eq!(result.response, Some(String::from("#4125,9,#")));
+
+        assert_eq!(sim.active_bank, MemoryBank::B);
+        assert_eq!(sim.fpgas[0].pattern_memory_a.words(1..5), [1, 2, 3, 4]);
+        assert_eq!(sim.fpgas[0].pattern_memory_b.words(1..5), [0x0A, 0x0B, 0x0C, 0x0D]);
+        assert_eq!(sim.fpgas[0].tristate_memory_b.words(5..9), [0, 0, 0, 1]);
+        assert_eq!(sim.fpgas[0].tristate_memory_a.words(5..9), [0; 4]);
+        assert_eq!(sim.loaded_pattern_range(MemoryBank::A), 1..5);
+        assert_eq!(sim.loaded_pattern_range(MemoryBank::B), 1..9);
+
+        // An unterminated load does not change the running bank.
+        sim.select_load_bank(MemoryBank::A);
+        sim.process_command(b"<C1F5000>").unwrap();
+        assert_eq!(sim.active_bank, MemoryBank::B);
+        // There is no wire command for bank B.
+        assert_eq!(sim.process_command(b"<C1F5004>").unwrap_err(), CommandError::InvalidParameter);
+    }
+
+    #[test]
+    fn pattern_memory_is_allocated_only_where_loaded() {
+        let mut sim = Simulator::new(0x1F);
+        let pages = |s: &Simulator| s.fpgas.iter().map(|f| f.pattern_memory_a.allocated_pages() + f.tristate_memory_a.allocated_pages()).sum::<usize>();
+        assert_eq!(pages(&sim), 0);
+
+        sim.process_command(b"<C1F5000>").unwrap();
+        sim.process_command(b"<P\x01\x00\x00\x00\x00\x02\x00\x00\x00\x00\x03\x00\x00\x00\x00\x04\x00\x00\x00\x00>").unwrap();
+        sim.process_command(b"<C1F5001>").unwrap();
+        assert_eq!(pages(&sim), 1);
+
+        // A clone shares the loaded data until one side writes to it.
+        let mut copy = sim.clone();
+        copy.fpgas[0].pattern_memory_a[2] = 0xFF;
+        assert_eq!(sim.fpgas[0].pattern_memory_a[2], 2);
+    }
 }
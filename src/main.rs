@@ -1,12 +1,19 @@
+mod headless;
+
 use crossterm::{
     event::{self, Event, KeyCode, KeyEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
-use ez_sim_lib::{CommandError, Simulator, ProcessResult};
+use ez_sim_lib::{
+    clock,
+    transport::{self, TransportMessage},
+    Bus, CommandError, HardwareProfile, SharedBus,
+};
 use ratatui::{prelude::*, widgets::*};
 use std::{
     io::{self, Write},
+    net::TcpListener,
     sync::{
         atomic::{AtomicBool, Ordering},
         mpsc::{self, Receiver, Sender},
@@ -23,9 +30,24 @@ enum AppMode {
     Manual,
     SerialSelect,
     SerialListen,
+    TcpSelect,
+    TcpListen,
+    PtyListen,
     Exiting,
 }
 
+// The entries of the main menu, in display order
+const MENU_ITEMS: [&str; 5] = [
+    "Manual Command Input",
+    "Listen on Serial Port",
+    "Listen on TCP Socket",
+    "Serve on Pseudo-Terminal",
+    "Exit",
+];
+
+// The address offered when entering TCP mode
+const DEFAULT_TCP_ADDRESS: &str = "127.0.0.1:5000";
+
 // Enum to represent which UI element is currently focused
 #[derive(PartialEq, Clone, Copy)]
 enum Focus {
@@ -36,15 +58,10 @@ enum Focus {
     BaudRateList,
 }
 
-// Messages for communication between the serial thread and the main TUI thread
-enum SerialMessage {
-    Log(String),
-    Error(String),
-}
-
 // The main application state for the TUI
-struct App<'a> {
-    simulator: &'a mut Simulator,
+struct App {
+    // The single board shared with any listener threads.
+    simulator: SharedBus,
     mode: AppMode,
     focus: Focus,
     logs: Vec<String>,
@@ -56,14 +73,19 @@ struct App<'a> {
     port_list_state: ListState,
     baud_rates: Vec<u32>,
     baud_rate_list_state: ListState,
-    serial_rx: Option<Receiver<SerialMessage>>,
-    serial_tx: Sender<SerialMessage>,
-    serial_thread_handle: Option<thread::JoinHandle<()>>,
-    serial_should_stop: Option<Arc<AtomicBool>>,
+    // --- TCP Mode State ---
+    tcp_address: String,
+    // --- PTY Mode State ---
+    pty_path: Option<String>,
+    // --- Listener thread state, shared by all transports ---
+    listener_rx: Option<Receiver<TransportMessage>>,
+    listener_tx: Sender<TransportMessage>,
+    listener_thread_handle: Option<thread::JoinHandle<()>>,
+    listener_should_stop: Option<Arc<AtomicBool>>,
 }
 
-impl<'a> App<'a> {
-    fn new(simulator: &'a mut Simulator) -> Self {
+impl App {
+    fn new(simulator: SharedBus) -> Self {
         let (tx, rx) = mpsc::channel();
         let mut port_list_state = ListState::default();
         port_list_state.select(Some(0));
@@ -83,10 +105,12 @@ impl<'a> App<'a> {
             // Invert the baud rates to show most common first
             baud_rates: vec![115200, 57600, 38400, 19200, 9600],
             baud_rate_list_state,
-            serial_rx: Some(rx),
-            serial_tx: tx,
-            serial_thread_handle: None,
-            serial_should_stop: None,
+            tcp_address: DEFAULT_TCP_ADDRESS.to_string(),
+            pty_path: None,
+            listener_rx: Some(rx),
+            listener_tx: tx,
+            listener_thread_handle: None,
+            listener_should_stop: None,
         }
     }
 
@@ -118,6 +142,9 @@ impl<'a> App<'a> {
                     CommandError::InvalidCommandId(_) => "Command ID is not a valid number.".to_string(),
                     CommandError::UnimplementedCommand(id) => format!("Command '{}' is not yet implemented.", id),
                     CommandError::InvalidParameter => "Command contains an invalid parameter.".to_string(),
+                    CommandError::PatternMemoryOverflow { capacity } => {
+                        format!("Pattern data overflows FPGA memory; only {} addresses can be loaded.", capacity)
+                    }
                 };
                 self.log(format!("[ERROR] {}", error_msg));
             }
@@ -138,38 +165,90 @@ impl<'a> App<'a> {
         }
     }
 
-    // Clean up the serial thread resources
-    fn stop_serial_thread(&mut self) {
-        if let Some(stop_flag) = self.serial_should_stop.take() {
+    // Run a transport on a background thread, sharing this app's simulator
+    fn spawn_listener<F>(&mut self, body: F)
+    where
+        F: FnOnce(SharedBus, Sender<TransportMessage>, Arc<AtomicBool>) + Send + 'static,
+    {
+        let simulator = self.simulator.clone();
+        let tx = self.listener_tx.clone();
+        let stop_flag = Arc::new(AtomicBool::new(false));
+        self.listener_should_stop = Some(stop_flag.clone());
+        self.listener_thread_handle = Some(thread::spawn(move || body(simulator, tx, stop_flag)));
+    }
+
+    // Clean up the listener thread resources
+    fn stop_listener_thread(&mut self) {
+        if let Some(stop_flag) = self.listener_should_stop.take() {
             stop_flag.store(true, Ordering::Relaxed);
         }
-        if let Some(handle) = self.serial_thread_handle.take() {
-            handle.join().expect("Failed to join serial thread");
+        if let Some(handle) = self.listener_thread_handle.take() {
+            handle.join().expect("Failed to join listener thread");
         }
     }
 }
 
+fn format_addresses(addresses: &[u8]) -> String {
+    addresses.iter().map(|a| format!("0x{:02X}", a)).collect::<Vec<_>>().join(", ")
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Any command-line arguments select headless mode.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        return match headless::parse_args(args) {
+            Ok(headless::Invocation::Run(options)) => headless::run(options),
+            Ok(headless::Invocation::Help) => {
+                println!("{}", headless::USAGE);
+                Ok(())
+            }
+            Err(message) => {
+                eprintln!("{}\n\n{}", message, headless::USAGE);
+                std::process::exit(2);
+            }
+        };
+    }
+
     println!("=========================");
     println!("  Endzone 250 Simulator  ");
     println!("=========================");
 
-    print!("Enter RS-485 address (hex, default: 1F): ");
+    print!("Enter RS-485 address(es) (hex, comma-separated, default: 1F): ");
     io::stdout().flush().unwrap();
 
     let mut addr_input = String::new();
     io::stdin().read_line(&mut addr_input).unwrap();
 
-    let simulator_address = match addr_input.trim() {
-        "" => 0x1F,
-        s => u8::from_str_radix(s, 16).unwrap_or_else(|_| {
-            eprintln!("[WARNING] Invalid hex address '{}'. Using default 0x1F.", s);
-            0x1F
-        }),
-    };
+    let mut addresses = Vec::new();
+    for s in addr_input.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        match u8::from_str_radix(s, 16) {
+            Ok(address) if !addresses.contains(&address) => addresses.push(address),
+            Ok(_) => eprintln!("[WARNING] Duplicate address '{}' ignored.", s),
+            Err(_) => eprintln!("[WARNING] Invalid hex address '{}' ignored.", s),
+        }
+    }
+    if addresses.is_empty() {
+        addresses.push(0x1F);
+    }
+
+    print!("Enter hardware profile file (blank for an empty board): ");
+    io::stdout().flush().unwrap();
+
+    let mut profile_input = String::new();
+    io::stdin().read_line(&mut profile_input).unwrap();
 
-    let mut simulator = Simulator::new(simulator_address);
-    println!("Simulator starting with Address: 0x{:02X}", simulator_address);
+    let mut bus = Bus::with_addresses(&addresses);
+    let profile_path = profile_input.trim();
+    if !profile_path.is_empty() {
+        let applied = HardwareProfile::load(profile_path)
+            .and_then(|profile| bus.boards_mut().iter_mut().try_for_each(|board| profile.apply(board)));
+        if let Err(e) = applied {
+            eprintln!("[WARNING] {}. Starting with an empty board.", e);
+        }
+    }
+
+    let simulator = SharedBus::new(bus);
+    println!("Simulator starting with Address(es): {}", format_addresses(&addresses));
     println!("Launching TUI...");
     std::thread::sleep(Duration::from_secs(1));
 
@@ -179,15 +258,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
-    let mut app = App::new(&mut simulator);
+    // Simulated time runs for the whole session, whichever mode is active.
+    let clock_stop = Arc::new(AtomicBool::new(false));
+    let clock_thread = clock::spawn_wall_clock(simulator.clone(), clock_stop.clone());
+
+    let mut app = App::new(simulator);
     let res = run_app(&mut terminal, &mut app);
 
     disable_raw_mode()?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     terminal.show_cursor()?;
 
-    // Ensure the thread is stopped on exit
-    app.stop_serial_thread();
+    // Ensure the threads are stopped on exit
+    app.stop_listener_thread();
+    clock_stop.store(true, Ordering::Relaxed);
+    clock_thread.join().expect("Failed to join clock thread");
 
     if let Err(err) = res {
         println!("Error: {:?}", err);
@@ -196,18 +281,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> io::Result<()> {
+fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App) -> io::Result<()> {
     let tick_rate = Duration::from_millis(250);
     let mut last_tick = Instant::now();
-    let rx = app.serial_rx.take().unwrap();
+    let rx = app.listener_rx.take().unwrap();
 
     loop {
         terminal.draw(|f| ui(f, app))?;
 
         if let Ok(message) = rx.try_recv() {
             match message {
-                SerialMessage::Log(msg) => app.log(msg),
-                SerialMessage::Error(err) => app.log(format!("[SERIAL ERROR] {}", err)),
+                TransportMessage::Log(msg) => app.log(msg),
+                TransportMessage::Error(err) => app.log(format!("[TRANSPORT ERROR] {}", err)),
             }
         }
 
@@ -222,7 +307,8 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> io::Res
                         AppMode::Menu => handle_menu_input(app, key),
                         AppMode::Manual => handle_manual_input(app, key),
                         AppMode::SerialSelect => handle_serial_select_input(app, key),
-                        AppMode::SerialListen => handle_serial_listen_input(app, key),
+                        AppMode::TcpSelect => handle_tcp_select_input(app, key),
+                        AppMode::SerialListen | AppMode::TcpListen | AppMode::PtyListen => handle_listen_input(app, key),
                         _ => {}
                     }
                 }
@@ -239,15 +325,14 @@ fn run_app<B: Backend>(terminal: &mut Terminal<B>, app: &mut App<'_>) -> io::Res
     }
 }
 
-fn handle_menu_input(app: &mut App<'_>, key: event::KeyEvent) {
-    let menu_items = ["Manual Command Input", "Listen on Serial Port", "Exit"];
+fn handle_menu_input(app: &mut App, key: event::KeyEvent) {
     match key.code {
         KeyCode::Char('q') => app.mode = AppMode::Exiting,
         KeyCode::Down => {
-            app.menu_selection = (app.menu_selection + 1) % menu_items.len();
+            app.menu_selection = (app.menu_selection + 1) % MENU_ITEMS.len();
         }
         KeyCode::Up => {
-            app.menu_selection = (app.menu_selection + menu_items.len() - 1) % menu_items.len();
+            app.menu_selection = (app.menu_selection + MENU_ITEMS.len() - 1) % MENU_ITEMS.len();
         }
         KeyCode::Enter => match app.menu_selection {
             0 => {
@@ -260,14 +345,19 @@ fn handle_menu_input(app: &mut App<'_>, key: event::KeyEvent) {
                 app.mode = AppMode::SerialSelect;
                 app.focus = Focus::SerialPortList;
             }
-            2 => app.mode = AppMode::Exiting,
+            2 => {
+                app.mode = AppMode::TcpSelect;
+                app.focus = Focus::Input;
+            }
+            3 => start_pty_listener(app),
+            4 => app.mode = AppMode::Exiting,
             _ => {}
         },
         _ => {}
     }
 }
 
-fn handle_manual_input(app: &mut App<'_>, key: event::KeyEvent) {
+fn handle_manual_input(app: &mut App, key: event::KeyEvent) {
     if key.code == KeyCode::Esc {
         app.mode = AppMode::Menu;
         app.focus = Focus::Menu;
@@ -290,11 +380,9 @@ fn handle_manual_input(app: &mut App<'_>, key: event::KeyEvent) {
             KeyCode::Backspace => {
                 app.input.pop();
             }
-            KeyCode::Enter => {
-                if !app.input.is_empty() {
-                    app.process_command(&app.input.clone());
-                    app.input.clear();
-                }
+            KeyCode::Enter if !app.input.is_empty() => {
+                app.process_command(&app.input.clone());
+                app.input.clear();
             }
             _ => {}
         },
@@ -317,7 +405,7 @@ fn handle_manual_input(app: &mut App<'_>, key: event::KeyEvent) {
     }
 }
 
-fn handle_serial_select_input(app: &mut App<'_>, key: event::KeyEvent) {
+fn handle_serial_select_input(app: &mut App, key: event::KeyEvent) {
     if key.code == KeyCode::Esc {
         app.mode = AppMode::Menu;
         app.focus = Focus::Menu;
@@ -367,72 +455,94 @@ fn handle_serial_select_input(app: &mut App<'_>, key: event::KeyEvent) {
             app.mode = AppMode::SerialListen;
             app.focus = Focus::Logs; // Default focus to logs for scrolling
 
-            let tx = app.serial_tx.clone();
-            let mut simulator_clone = app.simulator.clone();
-            let stop_flag = Arc::new(AtomicBool::new(false));
-            app.serial_should_stop = Some(stop_flag.clone());
-
-            let handle = thread::spawn(move || {
+            app.spawn_listener(move |simulator, tx, stop_flag| {
                 let port = serialport::new(&port_name, baud_rate)
-                    .timeout(Duration::from_millis(100))
+                    .timeout(transport::READ_TIMEOUT)
                     .open();
 
                 let mut port = match port {
                     Ok(p) => p,
                     Err(e) => {
-                        tx.send(SerialMessage::Error(format!("Failed to open port: {}", e))).unwrap();
+                        let _ = tx.send(TransportMessage::Error(format!("Failed to open port: {}", e)));
                         return;
                     }
                 };
 
-                let mut serial_buf: Vec<u8> = vec![0; 128];
-                while !stop_flag.load(Ordering::Relaxed) {
-                    match port.read(serial_buf.as_mut_slice()) {
-                        Ok(bytes_read) => {
-                            if bytes_read > 0 {
-                                let command_str = std::str::from_utf8(&serial_buf[..bytes_read]).unwrap_or("").trim();
-                                if !command_str.is_empty() {
-                                    tx.send(SerialMessage::Log(format!("> {}", command_str))).unwrap();
-                                    match simulator_clone.process_command(command_str.as_bytes()) {
-                                        Ok(result) => {
-                                            // Send any debug logs
-                                            for debug_log in result.logs {
-                                                tx.send(SerialMessage::Log(debug_log)).unwrap();
-                                            }
-                                            // Handle the actual response
-                                            if let Some(response) = result.response {
-                                                tx.send(SerialMessage::Log(format!("< {}", response))).unwrap();
-                                                if let Err(e) = port.write_all(response.as_bytes()) {
-                                                    tx.send(SerialMessage::Error(format!("Failed to write to port: {}", e))).unwrap();
-                                                }
-                                            }
-                                        }
-                                        Err(e) => {
-                                            tx.send(SerialMessage::Log(format!("[ERROR] {:?}", e))).unwrap();
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        Err(ref e) if e.kind() == io::ErrorKind::TimedOut => continue,
-                        Err(e) => {
-                            tx.send(SerialMessage::Error(format!("{}", e))).unwrap();
-                            break;
+                if let Err(e) = transport::serve(&mut port, &simulator, &tx, &stop_flag) {
+                    let _ = tx.send(TransportMessage::Error(format!("{}", e)));
+                }
+            });
+        }
+    }
+}
+
+fn handle_tcp_select_input(app: &mut App, key: event::KeyEvent) {
+    match key.code {
+        KeyCode::Esc => {
+            app.mode = AppMode::Menu;
+            app.focus = Focus::Menu;
+            app.log("TCP listen cancelled.".into());
+        }
+        KeyCode::Char(c) if !c.is_control() => app.tcp_address.push(c),
+        KeyCode::Backspace => {
+            app.tcp_address.pop();
+        }
+        KeyCode::Enter => {
+            let address = app.tcp_address.trim().to_string();
+            // Bind here rather than in the thread so a bad address is reported immediately.
+            match TcpListener::bind(&address) {
+                Ok(listener) => {
+                    app.log(format!("Listening for TCP clients on {}.", address));
+                    app.mode = AppMode::TcpListen;
+                    app.focus = Focus::Logs;
+                    app.spawn_listener(move |simulator, tx, stop_flag| {
+                        if let Err(e) = transport::serve_tcp(listener, simulator, tx.clone(), stop_flag) {
+                            let _ = tx.send(TransportMessage::Error(format!("{}", e)));
                         }
-                    }
+                    });
+                }
+                Err(e) => app.log(format!("[ERROR] Could not listen on {}: {}", address, e)),
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(unix)]
+fn start_pty_listener(app: &mut App) {
+    match ez_sim_lib::pty::Pty::open() {
+        Ok(mut pty) => {
+            let path = pty.slave_path().display().to_string();
+            app.log(format!("Serving on pseudo-terminal {}. Point the host software at this device.", path));
+            app.pty_path = Some(path);
+            app.mode = AppMode::PtyListen;
+            app.focus = Focus::Logs;
+            app.spawn_listener(move |simulator, tx, stop_flag| {
+                if let Err(e) = transport::serve(&mut pty, &simulator, &tx, &stop_flag) {
+                    let _ = tx.send(TransportMessage::Error(format!("{}", e)));
                 }
             });
-            app.serial_thread_handle = Some(handle);
         }
+        Err(e) => app.log(format!("[ERROR] Could not create pseudo-terminal: {}", e)),
     }
 }
 
-fn handle_serial_listen_input(app: &mut App<'_>, key: event::KeyEvent) {
+#[cfg(not(unix))]
+fn start_pty_listener(app: &mut App) {
+    app.log("[ERROR] Pseudo-terminals are only supported on Unix systems.".into());
+}
+
+fn handle_listen_input(app: &mut App, key: event::KeyEvent) {
     if key.code == KeyCode::Esc {
-        app.stop_serial_thread();
+        app.stop_listener_thread();
+        let message = match app.mode {
+            AppMode::TcpListen => "Stopped listening on TCP socket.",
+            AppMode::PtyListen => "Closed pseudo-terminal.",
+            _ => "Stopped listening on serial port.",
+        };
         app.mode = AppMode::Menu;
         app.focus = Focus::Menu;
-        app.log("Stopped listening on serial port.".into());
+        app.log(message.into());
         return;
     }
 
@@ -455,7 +565,7 @@ fn handle_serial_listen_input(app: &mut App<'_>, key: event::KeyEvent) {
 }
 
 
-fn ui(f: &mut Frame, app: &mut App<'_>) {
+fn ui(f: &mut Frame, app: &mut App) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints(
@@ -470,13 +580,16 @@ fn ui(f: &mut Frame, app: &mut App<'_>) {
         .split(f.size());
 
     let status_text = format!(
-        "Address: 0x{:02X} | Mode: {}",
-        app.simulator.rs485_address,
+        "Address: {} | Mode: {}",
+        format_addresses(&app.simulator.lock().addresses()),
         match app.mode {
             AppMode::Menu => "Menu",
             AppMode::Manual => "Manual Input",
             AppMode::SerialSelect => "Serial Port Select",
             AppMode::SerialListen => "Listening on Serial",
+            AppMode::TcpSelect => "TCP Address Entry",
+            AppMode::TcpListen => "Listening on TCP",
+            AppMode::PtyListen => "Serving on PTY",
             AppMode::Exiting => "Exiting",
         }
     );
@@ -490,6 +603,9 @@ fn ui(f: &mut Frame, app: &mut App<'_>) {
         AppMode::Manual => draw_manual_mode(f, app, chunks[1]),
         AppMode::SerialSelect => draw_serial_select(f, app, chunks[1]),
         AppMode::SerialListen => draw_serial_listen(f, app, chunks[1]),
+        AppMode::TcpSelect => draw_tcp_select(f, app, chunks[1]),
+        AppMode::TcpListen => draw_tcp_listen(f, app, chunks[1]),
+        AppMode::PtyListen => draw_pty_listen(f, app, chunks[1]),
         _ => {}
     }
 
@@ -499,7 +615,7 @@ fn ui(f: &mut Frame, app: &mut App<'_>) {
             Block::default()
                 .borders(Borders::ALL)
                 .title("Logs")
-                .border_style(if matches!(app.focus, Focus::Logs) || matches!(app.mode, AppMode::SerialListen) {
+                .border_style(if matches!(app.focus, Focus::Logs) || matches!(app.mode, AppMode::SerialListen | AppMode::TcpListen | AppMode::PtyListen) {
                     Style::default().fg(Color::Cyan)
                 } else {
                     Style::default()
@@ -518,16 +634,16 @@ fn ui(f: &mut Frame, app: &mut App<'_>) {
             _ => "Esc to return to menu.",
         },
         AppMode::SerialSelect => "Use ↑/↓ to navigate, Tab to switch panels, Enter to confirm, Esc to cancel.",
-        AppMode::SerialListen => "Listening... Use ↑/↓ to scroll logs, Esc to stop and return to menu.",
+        AppMode::SerialListen | AppMode::TcpListen | AppMode::PtyListen => "Listening... Use ↑/↓ to scroll logs, Esc to stop and return to menu.",
+        AppMode::TcpSelect => "Type the address to listen on, Enter to start, Esc to cancel.",
         _ => "'q' to quit.",
     };
     let footer = Paragraph::new(footer_text).style(Style::default().fg(Color::Cyan));
     f.render_widget(footer, chunks[3]);
 }
 
-fn draw_menu(f: &mut Frame, app: &mut App<'_>, area: Rect) {
-    let menu_items = ["Manual Command Input", "Listen on Serial Port", "Exit"];
-    let list_items: Vec<ListItem> = menu_items.iter().map(|&i| ListItem::new(i)).collect();
+fn draw_menu(f: &mut Frame, app: &mut App, area: Rect) {
+    let list_items: Vec<ListItem> = MENU_ITEMS.iter().map(|&i| ListItem::new(i)).collect();
 
     let list = List::new(list_items)
         .block(Block::default().borders(Borders::ALL).title("Main Menu"))
@@ -540,7 +656,7 @@ fn draw_menu(f: &mut Frame, app: &mut App<'_>, area: Rect) {
     f.render_stateful_widget(list, area, &mut list_state);
 }
 
-fn draw_manual_mode(f: &mut Frame, app: &mut App<'_>, area: Rect) {
+fn draw_manual_mode(f: &mut Frame, app: &mut App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
@@ -568,7 +684,7 @@ fn draw_manual_mode(f: &mut Frame, app: &mut App<'_>, area: Rect) {
     f.render_widget(instructions, chunks[1]);
 }
 
-fn draw_serial_select(f: &mut Frame, app: &mut App<'_>, area: Rect) {
+fn draw_serial_select(f: &mut Frame, app: &mut App, area: Rect) {
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
@@ -607,7 +723,7 @@ fn draw_serial_select(f: &mut Frame, app: &mut App<'_>, area: Rect) {
     f.render_stateful_widget(baud_list, chunks[1], &mut app.baud_rate_list_state);
 }
 
-fn draw_serial_listen(f: &mut Frame, app: &mut App<'_>, area: Rect) {
+fn draw_serial_listen(f: &mut Frame, app: &mut App, area: Rect) {
     let port_name = app.port_list_state.selected().map_or("N/A".to_string(), |i| app.available_ports.get(i).cloned().unwrap_or_default());
     let baud_rate = app.baud_rate_list_state.selected().map_or(0, |i| app.baud_rates[i]);
 
@@ -626,3 +742,56 @@ fn draw_serial_listen(f: &mut Frame, app: &mut App<'_>, area: Rect) {
         .block(Block::default().borders(Borders::ALL).title("Serial Monitor"));
     f.render_widget(paragraph, area);
 }
+
+fn draw_tcp_select(f: &mut Frame, app: &mut App, area: Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(0)].as_ref())
+        .split(area);
+
+    let input_paragraph = Paragraph::new(app.tcp_address.as_str()).block(
+        Block::default()
+            .borders(Borders::ALL)
+            .title("TCP Listen Address")
+            .border_style(Style::default().fg(Color::Cyan)),
+    );
+    f.render_widget(input_paragraph, chunks[0]);
+    f.set_cursor(chunks[0].x + app.tcp_address.len() as u16 + 1, chunks[0].y + 1);
+
+    let instructions = Paragraph::new("Enter the address and port to listen on.\nUse 0.0.0.0 to accept clients from other hosts or containers.\nAll clients share this simulator's board state.")
+        .wrap(Wrap { trim: true })
+        .block(Block::default().borders(Borders::ALL).title("Info"));
+    f.render_widget(instructions, chunks[1]);
+}
+
+fn draw_tcp_listen(f: &mut Frame, app: &mut App, area: Rect) {
+    let text = vec![
+        Line::from(""),
+        Line::from(Span::styled("Listening on TCP Socket", Style::default().add_modifier(Modifier::BOLD))),
+        Line::from(""),
+        Line::from(format!("  Address: {}", app.tcp_address.trim())),
+        Line::from(""),
+        Line::from("Check logs below for connections and incoming data."),
+    ];
+
+    let paragraph = Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title("TCP Monitor"));
+    f.render_widget(paragraph, area);
+}
+
+fn draw_pty_listen(f: &mut Frame, app: &mut App, area: Rect) {
+    let text = vec![
+        Line::from(""),
+        Line::from(Span::styled("Serving on Pseudo-Terminal", Style::default().add_modifier(Modifier::BOLD))),
+        Line::from(""),
+        Line::from(format!("  Device: {}", app.pty_path.as_deref().unwrap_or("N/A"))),
+        Line::from(""),
+        Line::from("Open this device from the host software in place of the serial port."),
+    ];
+
+    let paragraph = Paragraph::new(text)
+        .alignment(Alignment::Center)
+        .block(Block::default().borders(Borders::ALL).title("PTY Monitor"));
+    f.render_widget(paragraph, area);
+}
@@ -0,0 +1,222 @@
+//! # Pattern Memory
+//!
+//! Each FPGA has four 1M-word memories, but a test program rarely touches more
+//! than a small part of them. `PatternMemory` stores words in fixed-size pages
+//! that are only allocated when a non-zero word is first written to them; every
+//! other address reads as zero. Pages are shared between clones and copied on
+//! write, so cloning a `Simulator` costs a few pointers per memory.
+//!
+//! In snapshots a memory is stored as its length and the runs of non-zero
+//! words in it.
+
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    fmt,
+    ops::{Index, IndexMut, Range},
+    sync::Arc,
+};
+
+/// Words per page of pattern memory.
+pub const PAGE_WORDS: usize = 0x1000;
+
+type Page = [u32; PAGE_WORDS];
+
+static ZERO: u32 = 0;
+
+/// A word-addressed FPGA memory that allocates storage only where it is written.
+#[derive(Clone)]
+pub struct PatternMemory {
+    len: usize,
+    pages: Vec<Option<Arc<Page>>>,
+}
+
+impl PatternMemory {
+    /// Creates a memory of `len` words, all zero, with no pages allocated.
+    pub fn new(len: usize) -> Self {
+        Self { len, pages: vec![None; len.div_ceil(PAGE_WORDS)] }
+    }
+
+    /// The number of addressable words.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns true if the memory has no addressable words.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns the word at `address`, or `None` if it is out of range.
+    pub fn get(&self, address: usize) -> Option<u32> {
+        (address < self.len).then(|| self[address])
+    }
+
+    /// Returns the words at `addresses`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range reaches past the end of the memory.
+    pub fn words(&self, addresses: Range<usize>) -> Vec<u32> {
+        addresses.map(|address| self[address]).collect()
+    }
+
+    /// Zeroes every word and releases all pages.
+    pub fn clear(&mut self) {
+        self.pages.iter_mut().for_each(|page| *page = None);
+    }
+
+    /// The number of pages holding storage, for memory accounting.
+    pub fn allocated_pages(&self) -> usize {
+        self.pages.iter().filter(|page| page.is_some()).count()
+    }
+
+    // Yields each allocated page with the address of its first word.
+    fn allocated(&self) -> impl Iterator<Item = (usize, &Page)> {
+        self.pages.iter().enumerate().filter_map(|(i, page)| page.as_deref().map(|page| (i * PAGE_WORDS, page)))
+    }
+
+    fn check(&self, address: usize) {
+        assert!(address < self.len, "address 0x{:X} is outside the {:#X}-word pattern memory", address, self.len);
+    }
+}
+
+impl Index<usize> for PatternMemory {
+    type Output = u32;
+
+    fn index(&self, address: usize) -> &u32 {
+        self.check(address);
+        match &self.pages[address / PAGE_WORDS] {
+            Some(page) => &page[address % PAGE_WORDS],
+            None => &ZERO,
+        }
+    }
+}
+
+impl IndexMut<usize> for PatternMemory {
+    /// Allocates the page holding `address` if needed, copying it first if it
+    /// is shared with a clone.
+    fn index_mut(&mut self, address: usize) -> &mut u32 {
+        self.check(address);
+        let page = self.pages[address / PAGE_WORDS].get_or_insert_with(|| Arc::new([0; PAGE_WORDS]));
+        &mut Arc::make_mut(page)[address % PAGE_WORDS]
+    }
+}
+
+impl PartialEq for PatternMemory {
+    /// Memories are equal when every word is, however they are paged.
+    fn eq(&self, other: &Self) -> bool {
+        self.len == other.len
+            && self.pages.iter().zip(&other.pages).all(|(a, b)| match (a, b) {
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b) || a == b,
+                (Some(page), None) | (None, Some(page)) => page.iter().all(|&word| word == 0),
+                (None, None) => true,
+            })
+    }
+}
+
+impl fmt::Debug for PatternMemory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PatternMemory").field("len", &self.len).field("allocated_pages", &self.allocated_pages()).finish()
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Run {
+    start: u32,
+    words: Vec<u32>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SparseWords {
+    len: u32,
+    runs: Vec<Run>,
+}
+
+impl Serialize for PatternMemory {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut runs: Vec<Run> = Vec::new();
+        for (base, page) in self.allocated() {
+            for (offset, &word) in page.iter().enumerate().filter(|(_, &word)| word != 0) {
+                let address = base + offset;
+                match runs.last_mut() {
+                    Some(run) if run.start as usize + run.words.len() == address => run.words.push(word),
+                    _ => runs.push(Run { start: address as u32, words: vec![word] }),
+                }
+            }
+        }
+        SparseWords { len: self.len as u32, runs }.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PatternMemory {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let sparse = SparseWords::deserialize(deserializer)?;
+        let mut memory = PatternMemory::new(sparse.len as usize);
+        for run in sparse.runs {
+            let start = run.start as usize;
+            if start + run.words.len() > memory.len {
+                return Err(D::Error::custom(format!("run at 0x{:X} is outside the {}-word memory", start, sparse.len)));
+            }
+            for (address, word) in (start..).zip(run.words).filter(|&(_, word)| word != 0) {
+                memory[address] = word;
+            }
+        }
+        Ok(memory)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_zero_until_written() {
+        let mut memory = PatternMemory::new(0x100000);
+        assert_eq!(memory[0xFFFFF], 0);
+        assert_eq!(memory.get(0x100000), None);
+        assert_eq!(memory.allocated_pages(), 0);
+
+        memory[0x1234] = 7;
+        memory[0x1235] = 8;
+        assert_eq!(memory.words(0x1233..0x1237), [0, 7, 8, 0]);
+        assert_eq!(memory.allocated_pages(), 1);
+
+        memory.clear();
+        assert_eq!(memory[0x1234], 0);
+        assert_eq!(memory.allocated_pages(), 0);
+    }
+
+    #[test]
+    fn clones_share_pages_until_written() {
+        let mut original = PatternMemory::new(0x100000);
+        original[5] = 1;
+        let mut copy = original.clone();
+        assert!(Arc::ptr_eq(original.pages[0].as_ref().unwrap(), copy.pages[0].as_ref().unwrap()));
+
+        copy[5] = 2;
+        assert_eq!((original[5], copy[5]), (1, 2));
+        assert_ne!(original, copy);
+
+        // A page written back to zero still compares equal to an unallocated one.
+        copy[5] = 0;
+        assert_eq!(copy, PatternMemory::new(0x100000));
+    }
+
+    #[test]
+    #[should_panic(expected = "outside the 0x10-word pattern memory")]
+    fn writes_past_the_end_panic() {
+        PatternMemory::new(0x10)[0x10] = 1;
+    }
+
+    #[test]
+    fn serializes_runs_across_pages() {
+        let mut memory = PatternMemory::new(0x2000);
+        memory[PAGE_WORDS - 1] = 1;
+        memory[PAGE_WORDS] = 2;
+        memory[0x1FFF] = 3;
+
+        let text = serde_json::to_string(&memory).unwrap();
+        assert_eq!(text, r#"{"len":8192,"runs":[{"start":4095,"words":[1,2]},{"start":8191,"words":[3]}]}"#);
+        assert_eq!(serde_json::from_str::<PatternMemory>(&text).unwrap(), memory);
+    }
+}
@@ -0,0 +1,291 @@
+//! # Hardware Profiles
+//!
+//! A fresh `Simulator` describes an empty board: no FPGAs, clock, sine-wave or
+//! analog modules are fitted and every version and data code is zero. A profile
+//! is a TOML file describing what a real board has fitted, so `C18` and `C21`
+//! report a populated board and the module-dependent paths can be exercised.
+//!
+//! ```toml
+//! fw_version = 1.46
+//! back_panel_address = 0x0A
+//! bib_code = 0xABC
+//! psu_data_codes = [1, 2, 3, 4, 5, 6]
+//!
+//! [[fpga]]
+//! slot = 1
+//! position = 1
+//! version = 3
+//!
+//! [[clock_module]]
+//! slot = 2
+//! module_type = 0x2B
+//! fpga_version = 4
+//!
+//! [[sine_wave]]
+//! slot = 1
+//! module_type = 0x3C
+//! fpga_version = 2
+//!
+//! [amon]
+//! module_type = 0x4D
+//! ```
+//!
+//! Slots are 1-based. Listed modules are fitted and anything omitted keeps its
+//! current value, so a profile can be applied on top of a board's defaults.
+
+use crate::Simulator;
+use serde::Deserialize;
+use std::{fmt, fs, io, path::Path};
+
+/// The fitted modules and identity of a simulated board.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct HardwareProfile {
+    pub fw_version: Option<f32>,
+    pub back_panel_address: Option<u8>,
+    pub bib_code: Option<u16>,
+    pub psu_data_codes: Option<[u8; 6]>,
+    #[serde(rename = "fpga")]
+    pub fpgas: Vec<FpgaProfile>,
+    #[serde(rename = "clock_module")]
+    pub clock_modules: Vec<ClockModuleProfile>,
+    #[serde(rename = "sine_wave")]
+    pub sine_waves: Vec<SineWaveProfile>,
+    pub amon: Option<AmonProfile>,
+}
+
+/// A fitted pattern FPGA.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FpgaProfile {
+    pub slot: usize,
+    #[serde(default)]
+    pub position: u8,
+    #[serde(default)]
+    pub version: u8,
+}
+
+/// A fitted clock module.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClockModuleProfile {
+    pub slot: usize,
+    #[serde(default)]
+    pub module_type: u8,
+    #[serde(default)]
+    pub fpga_version: u8,
+}
+
+/// A fitted sine-wave module. Its FPGA is programmed unless stated otherwise.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SineWaveProfile {
+    pub slot: usize,
+    #[serde(default)]
+    pub module_type: u8,
+    #[serde(default)]
+    pub fpga_version: u8,
+    #[serde(default = "programmed_by_default")]
+    pub programmed: bool,
+}
+
+fn programmed_by_default() -> bool {
+    true
+}
+
+/// A fitted analog monitor module.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AmonProfile {
+    #[serde(default)]
+    pub module_type: u8,
+}
+
+/// Why a profile could not be loaded.
+#[derive(Debug)]
+pub enum ProfileError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The file is not a valid profile.
+    Parse(toml::de::Error),
+    /// A module names a slot the board does not have, or one already used.
+    InvalidSlot { module: &'static str, slot: usize },
+}
+
+impl fmt::Display for ProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProfileError::Io(e) => write!(f, "cannot read profile: {}", e),
+            ProfileError::Parse(e) => write!(f, "invalid profile: {}", e),
+            ProfileError::InvalidSlot { module, slot } => write!(f, "invalid profile: {} slot {} is out of range or repeated", module, slot),
+        }
+    }
+}
+
+impl std::error::Error for ProfileError {}
+
+impl From<io::Error> for ProfileError {
+    fn from(e: io::Error) -> Self {
+        ProfileError::Io(e)
+    }
+}
+
+// Checks that every slot is in 1..=count and used once.
+fn check_slots(module: &'static str, slots: impl Iterator<Item = usize>, count: usize) -> Result<(), ProfileError> {
+    let mut used = vec![false; count];
+    for slot in slots {
+        match used.get_mut(slot.wrapping_sub(1)) {
+            Some(used) if !*used => *used = true,
+            _ => return Err(ProfileError::InvalidSlot { module, slot }),
+        }
+    }
+    Ok(())
+}
+
+impl HardwareProfile {
+    /// Parses and validates a profile from TOML text.
+    pub fn from_toml(text: &str) -> Result<Self, ProfileError> {
+        let profile: Self = toml::from_str(text).map_err(ProfileError::Parse)?;
+        profile.check()?;
+        Ok(profile)
+    }
+
+    /// Checks that every module names a slot the board has, each used once.
+    pub fn check(&self) -> Result<(), ProfileError> {
+        check_slots("fpga", self.fpgas.iter().map(|m| m.slot), 2)?;
+        check_slots("clock_module", self.clock_modules.iter().map(|m| m.slot), 4)?;
+        check_slots("sine_wave", self.sine_waves.iter().map(|m| m.slot), 2)
+    }
+
+    /// Reads and validates a profile file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ProfileError> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Fits the profile's modules to `sim` and sets its identity values. A
+    /// profile built in code is checked first, and `sim` is left untouched if
+    /// any slot is invalid.
+    pub fn apply(&self, sim: &mut Simulator) -> Result<(), ProfileError> {
+        self.check()?;
+        if let Some(fw_version) = self.fw_version { sim.fw_version = fw_version; }
+        if let Some(address) = self.back_panel_address { sim.back_panel_address = address; }
+        if let Some(bib_code) = self.bib_code { sim.bib_code = bib_code; }
+        if let Some(codes) = self.psu_data_codes { sim.psu_data_codes = codes; }
+
+        for m in &self.fpgas {
+            let fpga = &mut sim.fpgas[m.slot - 1];
+            fpga.present = true;
+            fpga.position = m.position;
+            fpga.version = m.version;
+        }
+        for m in &self.clock_modules {
+            let gen = &mut sim.clock_generators[m.slot - 1];
+            gen.present = true;
+            gen.module_type = m.module_type;
+            gen.fpga_version = m.fpga_version;
+        }
+        for m in &self.sine_waves {
+            let sw = &mut sim.sine_waves[m.slot - 1];
+            sw.present = true;
+            sw.module_type = m.module_type;
+            sw.fpga_version = m.fpga_version;
+            sw.programmed = m.programmed;
+        }
+        if let Some(amon) = &self.amon {
+            sim.amon_present = true;
+            sim.amon_type = amon.module_type;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applied_profile_populates_configuration() {
+        let profile = HardwareProfile::from_toml(
+            r#"
+            back_panel_address = 0x0A
+            bib_code = 0xABC
+            psu_data_codes = [1, 2, 3, 4, 5, 6]
+
+            [[fpga]]
+            slot = 1
+            position = 1
+
+            [[clock_module]]
+            slot = 2
+            module_type = 0x2B
+
+            [[sine_wave]]
+            slot = 1
+            module_type = 0x3C
+
+            [amon]
+            module_type = 0x4D
+            "#,
+        )
+        .unwrap();
+        let mut sim = Simulator::new(0x1F);
+        profile.apply(&mut sim).unwrap();
+
+        let result = sim.process_command(b"<C1F18>").unwrap();
+        let expected = "#10A,11F,1ABC,1,1,101,102,103,104,105,106,1,1,0,0,0,100,1,12B,0,100,0,100,1,13C,0,100,1,14D,0,0,0,0,1,0#";
+        assert_eq!(result.response, Some(expected.to_string()));
+    }
+
+    #[test]
+    fn applied_profile_sets_versions() {
+        let profile = HardwareProfile::from_toml(
+            r#"
+            fw_version = 2.5
+
+            [[fpga]]
+            slot = 1
+            version = 3
+            [[fpga]]
+            slot = 2
+            version = 4
+
+            [[clock_module]]
+            slot = 4
+            fpga_version = 9
+            "#,
+        )
+        .unwrap();
+        let mut sim = Simulator::new(0x1F);
+        profile.apply(&mut sim).unwrap();
+
+        assert!(sim.has_dual_fpga());
+        let result = sim.process_command(b"<C1F21>").unwrap().response.unwrap();
+        assert!(result.starts_with("#102.50,103,104,100,100,100,109,"), "{}", result);
+    }
+
+    #[test]
+    fn rejects_invalid_profiles() {
+        assert!(matches!(HardwareProfile::from_toml("[[fpga]]\nslot = 3"), Err(ProfileError::InvalidSlot { module: "fpga", slot: 3 })));
+        assert!(matches!(
+            HardwareProfile::from_toml("[[sine_wave]]\nslot = 1\n[[sine_wave]]\nslot = 1"),
+            Err(ProfileError::InvalidSlot { module: "sine_wave", slot: 1 })
+        ));
+        assert!(matches!(HardwareProfile::from_toml("[[clock_module]]\nslot = 0"), Err(ProfileError::InvalidSlot { .. })));
+        assert!(matches!(HardwareProfile::from_toml("bib = 1"), Err(ProfileError::Parse(_))));
+        assert!(matches!(HardwareProfile::load("/nonexistent/profile.toml"), Err(ProfileError::Io(_))));
+    }
+
+    #[test]
+    fn apply_rejects_invalid_slots_in_code_built_profiles() {
+        let mut sim = Simulator::new(0x1F);
+        for slot in [0, 3] {
+            let profile = HardwareProfile {
+                bib_code: Some(0x123),
+                fpgas: vec![FpgaProfile { slot, position: 1, version: 1 }],
+                ..Default::default()
+            };
+            assert!(matches!(profile.apply(&mut sim), Err(ProfileError::InvalidSlot { module: "fpga", .. })));
+        }
+        assert_eq!(sim.bib_code, Simulator::new(0x1F).bib_code);
+    }
+}
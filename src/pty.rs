@@ -0,0 +1,164 @@
+//! # Pseudo-Terminal Transport
+//!
+//! Creates a PTY pair so host software that only knows how to open a tty device
+//! can talk to the simulator without a USB-RS485 adapter. The simulator serves
+//! the master side with `transport::serve`; the host opens the slave path.
+
+use crate::transport::READ_TIMEOUT;
+use std::{
+    ffi::CStr,
+    fs::{File, OpenOptions},
+    io::{self, Read, Write},
+    os::unix::{fs::OpenOptionsExt, io::{AsRawFd, FromRawFd}},
+    path::{Path, PathBuf},
+};
+
+/// The master side of a pseudo-terminal pair.
+#[derive(Debug)]
+pub struct Pty {
+    master: File,
+    // Holding the slave open keeps the pair alive between host connections, so
+    // master reads wait for data instead of failing with EIO when the host closes it.
+    _slave: File,
+    slave_path: PathBuf,
+}
+
+fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+    if result < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(result)
+    }
+}
+
+impl Pty {
+    /// Allocates a new PTY pair with the slave in raw mode.
+    pub fn open() -> io::Result<Self> {
+        // SAFETY: posix_openpt returns a fresh descriptor which the File takes ownership of.
+        let master = unsafe { File::from_raw_fd(check(libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY))?) };
+        let fd = master.as_raw_fd();
+
+        // SAFETY: fd is a valid PTY master for the duration of these calls.
+        unsafe {
+            check(libc::grantpt(fd))?;
+            check(libc::unlockpt(fd))?;
+        }
+        let slave_path = slave_name(fd)?;
+
+        let slave = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&slave_path)?;
+
+        // The protocol is binary ('P'/'R' frames), so disable echo, line editing and
+        // CR/LF translation in case the host does not configure the tty itself.
+        // SAFETY: termios is plain data filled in by tcgetattr before use.
+        unsafe {
+            let mut termios: libc::termios = std::mem::zeroed();
+            check(libc::tcgetattr(slave.as_raw_fd(), &mut termios))?;
+            libc::cfmakeraw(&mut termios);
+            check(libc::tcsetattr(slave.as_raw_fd(), libc::TCSANOW, &termios))?;
+        }
+
+        Ok(Self { master, _slave: slave, slave_path })
+    }
+
+    /// The device path the host software should open, such as `/dev/pts/3`.
+    pub fn slave_path(&self) -> &Path {
+        &self.slave_path
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn slave_name(fd: libc::c_int) -> io::Result<PathBuf> {
+    let mut buf = [0 as libc::c_char; 128];
+    // SAFETY: buf is writable for its full length and ptsname_r NUL-terminates on success.
+    let result = unsafe { libc::ptsname_r(fd, buf.as_mut_ptr(), buf.len()) };
+    if result != 0 {
+        return Err(io::Error::from_raw_os_error(result));
+    }
+    // SAFETY: ptsname_r succeeded, so buf holds a NUL-terminated string.
+    let name = unsafe { CStr::from_ptr(buf.as_ptr()) };
+    Ok(PathBuf::from(name.to_string_lossy().into_owned()))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn slave_name(fd: libc::c_int) -> io::Result<PathBuf> {
+    // ptsname uses a static buffer; it is only called while opening a PTY.
+    // SAFETY: fd is a valid PTY master and the result is copied out immediately.
+    let name = unsafe { libc::ptsname(fd) };
+    if name.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: ptsname returned a non-null, NUL-terminated string.
+    Ok(PathBuf::from(unsafe { CStr::from_ptr(name) }.to_string_lossy().into_owned()))
+}
+
+impl Read for Pty {
+    /// Waits up to `READ_TIMEOUT` for data, returning `TimedOut` if none arrives,
+    /// to match the behaviour of a serial port opened with a timeout.
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut poll_fd = libc::pollfd { fd: self.master.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        // SAFETY: poll_fd is a single valid pollfd.
+        let ready = check(unsafe { libc::poll(&mut poll_fd, 1, READ_TIMEOUT.as_millis() as libc::c_int) })?;
+        if ready == 0 {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.master.read(buf)
+    }
+}
+
+impl Write for Pty {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.master.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.master.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{transport, SharedSimulator, Simulator};
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc, Arc,
+        },
+        thread,
+    };
+
+    #[test]
+    fn host_can_talk_to_simulator_through_slave() {
+        let mut pty = Pty::open().unwrap();
+        let slave_path = pty.slave_path().to_path_buf();
+        assert!(slave_path.starts_with("/dev"));
+
+        let simulator = SharedSimulator::new(Simulator::new(0x1F));
+        let (tx, _rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let server = {
+            let simulator = simulator.clone();
+            let stop = stop.clone();
+            thread::spawn(move || transport::serve(&mut pty, &simulator, &tx, &stop))
+        };
+
+        // Act as the unmodified host software: open the tty and exchange a frame.
+        let mut host = OpenOptions::new().read(true).write(true).open(&slave_path).unwrap();
+        host.write_all(b"<C1F03>").unwrap();
+        let mut response = Vec::new();
+        let mut byte = [0u8; 1];
+        while response.len() < 4 {
+            host.read_exact(&mut byte).unwrap();
+            response.push(byte[0]);
+        }
+        assert_eq!(response, b"#ON#".to_vec());
+        assert!(simulator.lock().sequence_on);
+
+        stop.store(true, Ordering::Relaxed);
+        server.join().unwrap().unwrap();
+    }
+}
@@ -0,0 +1,288 @@
+//! # Configuration Reports
+//!
+//! Renders the driver configuration loaded by a `C50 02` ... `C50 03` download
+//! in engineering units, so a download can be checked without reading raw
+//! `Simulator` fields. A report is a list of titled sections of key/value
+//! entries; two reports can be diffed to see what a download changed, or how a
+//! simulator differs from an expected configuration.
+//!
+//! Values the firmware keeps as raw register words (main clock, FRC, output
+//! routing) are shown in hex.
+
+use crate::{AmonTest, Psu, Simulator};
+use std::fmt;
+
+/// One titled group of report entries, such as a single PSU.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    pub title: String,
+    pub entries: Vec<(String, String)>,
+}
+
+impl Section {
+    fn new(title: impl Into<String>) -> Self {
+        Self { title: title.into(), entries: Vec::new() }
+    }
+
+    fn add(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        self.entries.push((key.into(), value.into()));
+    }
+}
+
+/// The driver configuration of a simulator, rendered in engineering units.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigReport {
+    pub sections: Vec<Section>,
+}
+
+/// One entry that differs between two reports. `None` means the entry is
+/// absent from that report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportChange {
+    pub section: String,
+    pub key: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "enabled" } else { "disabled" }
+}
+
+fn psu_section(n: usize, psu: &Psu) -> Section {
+    let mut section = Section::new(format!("PSU {}", n));
+    for step in 1..=4 {
+        let dac = psu.step_voltage(step);
+        section.add(format!("Step {}", step), format!("{:.2} V (DAC 0x{:03X})", psu.output_voltage(dac as f32), dac));
+    }
+    section.add("Voltage window", format!("{:.2} V .. {:.2} V", psu.low_voltage_limit, psu.high_voltage_limit));
+    section.add("Current limit", format!("{:.2} A", psu.current_monitor_limit));
+    section.add("Voltage cal", format!("gain {:.4}, offset {:+.2} V", psu.psu_cal_val, psu.v_cal_offset_val));
+    section.add("Current cal", format!("gain {:.3}, offset {:+.2} A", psu.i_cal_val, psu.i_cal_offset_val));
+    section.add("Sequence", format!("position {}, delay {} ms", psu.sequence_id, psu.sequence_delay));
+    section.add("Micro-step", format!("{} steps, {} ms apart", psu.ustep_steps, psu.ustep_delay));
+    section
+}
+
+fn amon_test_section(n: usize, test: &AmonTest) -> Section {
+    let mut section = Section::new(format!("AMON test {}", n));
+    section.add("Type", test.test_type.to_string());
+    section.add("PSU link", test.psu_link.to_string());
+    section.add(
+        "TP1",
+        format!("mux {}, AMON mux {}/{}, gain {:.3}", test.tp1_mux_ch, test.tp1_amon_mux_a, test.tp1_amon_mux_b, test.tp1_gain),
+    );
+    section.add(
+        "TP2",
+        format!("mux {}, AMON mux {}/{}, gain {:.3}", test.tp2_mux_ch, test.tp2_amon_mux_a, test.tp2_amon_mux_b, test.tp2_gain),
+    );
+    section.add("Sum gain", format!("{:.3}", test.sum_gain));
+    section.add("Cal", format!("gain {:.4}, offset {:+.4}", test.cal_gain, test.cal_offset));
+    section.add("Limits", format!("{:.4} .. {:.4}", test.low_limit, test.high_limit));
+    section
+}
+
+impl ConfigReport {
+    /// Builds a report of the configuration currently loaded into `sim`.
+    pub fn new(sim: &Simulator) -> Self {
+        let mut sections: Vec<Section> = sim.psus.iter().enumerate().map(|(i, psu)| psu_section(i + 1, psu)).collect();
+        let config = &sim.system_config;
+
+        let mut sequence = Section::new("Sequence");
+        let order: Vec<String> = sim.power_up_order().iter().map(|&i| format!("PSU {}", i + 1)).collect();
+        sequence.add("Power-up order", order.join(", "));
+        sequence.add("PSU sequencing", on_off(config.psu_sequence_enabled));
+        sequence.add("PSU stepping", format!("{}, {} ms between steps", on_off(config.psu_step_enabled), config.psu_step_delay));
+        sequence.add("Micro-stepping", on_off(sim.ustep_enabled));
+        sequence.add("Power-up delay", format!("{} ms", config.power_up_delay));
+        sequence.add("Set point", on_off(config.set_point_enabled));
+        for (n, (on, off)) in [
+            (config.seq_on_delay_1, config.seq_off_delay_1),
+            (config.seq_on_delay_2, config.seq_off_delay_2),
+            (config.seq_on_delay_3, config.seq_off_delay_3),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            sequence.add(format!("Signal delay {}", n + 1), format!("on {}, off {}", on, off));
+        }
+        sequence.add("Signal modules", format!("on {}, off {}", config.sigs_mod_sequence_on, config.sigs_mod_sequence_off));
+        let stops: Vec<&str> = [
+            (config.stop_on_v_error, "voltage"),
+            (config.stop_on_i_error, "current"),
+            (config.stop_on_clk_error, "clock"),
+            (config.stop_on_temp_error, "temperature"),
+        ]
+        .into_iter()
+        .filter_map(|(stop, name)| stop.then_some(name))
+        .collect();
+        sequence.add("Stop on", if stops.is_empty() { "none".to_string() } else { stops.join(", ") });
+        sequence.add(
+            "Auto reset",
+            if config.auto_reset { format!("{} retries", config.auto_reset_retries) } else { "disabled".to_string() },
+        );
+        sections.push(sequence);
+
+        // Timers count in units of `timer_unit`, which the firmware does not pin down.
+        let mut timers = Section::new("Timers");
+        timers.add("Unit", format!("{} s", sim.timer_unit.as_secs_f64()));
+        for (i, (timer, alarm)) in sim.timer_values.iter().zip(sim.alarm_values.iter()).enumerate() {
+            timers.add(format!("Timer {}", i + 1), format!("{}, alarm at {}", timer, alarm));
+        }
+        sections.push(timers);
+
+        let mut ptc = Section::new("PTC");
+        ptc.add("State", on_off(sim.ptc_config.enabled));
+        ptc.add("Cycle", format!("{} s on, {} s off", sim.ptc_config.on_time_seconds, sim.ptc_config.off_time_seconds));
+        sections.push(ptc);
+
+        let mut clocks = Section::new("Clocks");
+        let main = &sim.main_clock_config;
+        clocks.add("Main frequency", format!("0x{:02X}{:02X}", main.freq_high_byte, main.freq_low_byte));
+        clocks.add("Main period", format!("0x{:02X}{:02X}", main.period_high_byte, main.period_low_byte));
+        clocks.add("Main source", main.source.to_string());
+        clocks.add("Clocks required", if config.clocks_required { "yes" } else { "no" });
+        clocks.add(
+            "Restart",
+            if config.clocks_restart_required { format!("after {} s", config.clocks_restart_time) } else { "disabled".to_string() },
+        );
+        clocks.add("Monitored 1-32", format!("0x{:08X}", !config.clk32_mon_filter));
+        clocks.add("Monitored 33-64", format!("0x{:08X}", !config.clk64_mon_filter));
+        let frc = &sim.frc_config;
+        clocks.add("FRC 1-4", format!("frequency 0x{:08X}, period 0x{:08X}, source 0x{:08X}", frc.frequency_1_4, frc.period_1_4, frc.source_1_4));
+        clocks.add("FRC 5-8", format!("frequency 0x{:08X}, period 0x{:08X}, source 0x{:08X}", frc.frequency_5_8, frc.period_5_8, frc.source_5_8));
+        sections.push(clocks);
+
+        let mut loops = Section::new("Pattern loops");
+        loops.add("Enables", format!("0x{:02X}", sim.loop_enables));
+        for (i, p_loop) in sim.pattern_loops.iter().enumerate() {
+            loops.add(format!("Loop {}", i + 1), format!("0x{:X} .. 0x{:X} x {}", p_loop.start_address, p_loop.end_address, p_loop.count));
+        }
+        loops.add("Repeat counts", format!("{}, {}", sim.repeat_count_1, sim.repeat_count_2));
+        sections.push(loops);
+
+        let mut routing = Section::new("Output routing");
+        for (i, value) in sim.output_routing.iter().enumerate() {
+            routing.add(format!("Group {}", i + 1), format!("0x{:08X}", value));
+        }
+        sections.push(routing);
+
+        // Only configured tests are listed; the firmware keeps a fixed table of 100.
+        let mut amon = Section::new("AMON");
+        amon.add("Test count", sim.amon_test_count.to_string());
+        sections.push(amon);
+        for (i, test) in sim.amon_tests.iter().enumerate() {
+            if *test != AmonTest::default() {
+                sections.push(amon_test_section(i + 1, test));
+            }
+        }
+
+        Self { sections }
+    }
+
+    /// Returns the value of `key` in the section titled `section`.
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        let section = self.sections.iter().find(|s| s.title == section)?;
+        section.entries.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+    }
+
+    /// Lists the entries that differ from `self` (before) to `other` (after),
+    /// in report order.
+    pub fn diff(&self, other: &ConfigReport) -> Vec<ReportChange> {
+        let mut changes = Vec::new();
+        let mut record = |section: &Section, key: &str, before: Option<&str>, after: Option<&str>| {
+            if before != after {
+                changes.push(ReportChange {
+                    section: section.title.clone(),
+                    key: key.to_string(),
+                    before: before.map(String::from),
+                    after: after.map(String::from),
+                });
+            }
+        };
+        for section in &self.sections {
+            for (key, value) in &section.entries {
+                record(section, key, Some(value), other.get(&section.title, key));
+            }
+        }
+        for section in &other.sections {
+            for (key, value) in &section.entries {
+                if self.get(&section.title, key).is_none() {
+                    record(section, key, None, Some(value));
+                }
+            }
+        }
+        changes
+    }
+}
+
+impl fmt::Display for ConfigReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, section) in self.sections.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "[{}]", section.title)?;
+            for (key, value) in &section.entries {
+                writeln!(f, "  {:<16} {}", key, value)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ReportChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let show = |value: &Option<String>| value.clone().unwrap_or_else(|| "(absent)".to_string());
+        write!(f, "{} / {}: {} -> {}", self.section, self.key, show(&self.before), show(&self.after))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn reports_downloaded_psu_settings_in_engineering_units() {
+        let mut sim = Simulator::new(0x1F);
+        sim.process_command(b"<C1F5002>").unwrap();
+        // PSU 2: step 4 at DAC 0x800, first in sequence after 100 ms, unity cal
+        // gain and a 4.5-5.5V window in tenths of a volt.
+        sim.process_command(b"<Vxx0200800000000000>").unwrap();
+        sim.process_command(b"<Qxx020641271002D03700>").unwrap();
+        sim.process_command(b"<C1F5003>").unwrap();
+
+        let report = ConfigReport::new(&sim);
+        assert_eq!(report.get("PSU 2", "Step 4"), Some("5.00 V (DAC 0x800)"));
+        assert_eq!(report.get("PSU 2", "Voltage window"), Some("4.50 V .. 5.50 V"));
+        assert_eq!(report.get("PSU 2", "Voltage cal"), Some("gain 1.0000, offset +0.00 V"));
+        assert_eq!(report.get("PSU 2", "Sequence"), Some("position 1, delay 100 ms"));
+        assert_eq!(report.get("Sequence", "Power-up order"), Some("PSU 1, PSU 2, PSU 3, PSU 4, PSU 5, PSU 6"));
+        assert!(report.to_string().contains("[PSU 2]\n  Step 1           0.00 V (DAC 0x000)\n"));
+    }
+
+    #[test]
+    fn diff_lists_changed_and_added_entries() {
+        let before_sim = Simulator::new(0x1F);
+        let mut after_sim = before_sim.clone();
+        after_sim.ptc_config.enabled = true;
+        after_sim.timer_values[2] = 30;
+        after_sim.amon_tests[4].test_type = 2;
+
+        let before = ConfigReport::new(&before_sim);
+        let after = ConfigReport::new(&after_sim);
+        assert!(before.diff(&before).is_empty());
+
+        let changes: Vec<String> = before.diff(&after).iter().map(ToString::to_string).collect();
+        assert_eq!(changes[0], "Timers / Timer 3: 0, alarm at 0 -> 30, alarm at 0");
+        assert_eq!(changes[1], "PTC / State: disabled -> enabled");
+        assert_eq!(changes[2], "AMON test 5 / Type: (absent) -> 2");
+        assert_eq!(changes.len(), 9);
+
+        after_sim.timer_unit = Duration::from_millis(500);
+        let changes = after.diff(&ConfigReport::new(&after_sim));
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].to_string(), "Timers / Unit: 60 s -> 0.5 s");
+    }
+}
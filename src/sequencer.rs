@@ -0,0 +1,300 @@
+//! # Pattern Sequencer
+//!
+//! Models how the pattern FPGAs play back loaded pattern memory, so the vector
+//! stream a driver configuration produces can be checked cycle by cycle.
+//!
+//! The sequencer plays the active bank unless another is chosen. Execution
+//! starts at the first loaded address (1) and steps through to the last one.
+//! Each loop enabled in `loop_enables` (bit 0 for loop 1) jumps back from its
+//! end address to its start address until its body has run `count` times.
+//! Loops may be nested or disjoint but must not partly overlap; where several
+//! end on the same address the innermost is handled first. The whole pattern
+//! then runs `repeat_count_2:repeat_count_1` times, a 64-bit count in which 0
+//! still runs the pattern once. Each vector lasts one main clock period.
+
+use crate::{MemoryBank, PatternLoop, Simulator};
+use std::fmt;
+
+/// A loop configuration the sequencer cannot run. Loops are numbered from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LoopError {
+    /// No pattern data has been loaded.
+    NothingLoaded,
+    /// The loop's end address comes before its start address.
+    EndBeforeStart { number: usize, start: u32, end: u32 },
+    /// The loop reaches outside the loaded addresses.
+    OutsideLoadedRange { number: usize, start: u32, end: u32 },
+    /// The loop body would run zero times.
+    ZeroCount { number: usize },
+    /// Two loops overlap without one containing the other.
+    PartialOverlap { first: usize, second: usize },
+}
+
+impl fmt::Display for LoopError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoopError::NothingLoaded => write!(f, "no pattern data is loaded"),
+            LoopError::EndBeforeStart { number, start, end } => {
+                write!(f, "loop {} ends at 0x{:X}, before its start 0x{:X}", number, end, start)
+            }
+            LoopError::OutsideLoadedRange { number, start, end } => {
+                write!(f, "loop {} (0x{:X}..0x{:X}) is outside the loaded pattern", number, start, end)
+            }
+            LoopError::ZeroCount { number } => write!(f, "loop {} has a zero count", number),
+            LoopError::PartialOverlap { first, second } => write!(f, "loops {} and {} partly overlap", first, second),
+        }
+    }
+}
+
+impl std::error::Error for LoopError {}
+
+/// One vector played by the sequencer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vector {
+    /// The clock cycle the vector is played on, counting from 0.
+    pub cycle: u64,
+    pub address: u32,
+    /// The pattern word of each FPGA. FPGA 2's is 0 when it is not fitted.
+    pub pattern: [u32; 2],
+    /// The tristate word of each FPGA, a set bit disabling the pin.
+    pub tristate: [u32; 2],
+}
+
+#[derive(Debug, Clone)]
+struct ActiveLoop {
+    start: u32,
+    end: u32,
+    count: u32,
+    // Passes left including the current one.
+    remaining: u32,
+}
+
+/// Plays back the loaded pattern memory of a simulator as a stream of vectors.
+#[derive(Debug, Clone)]
+pub struct Sequencer<'a> {
+    sim: &'a Simulator,
+    bank: MemoryBank,
+    // Enabled loops, innermost first among loops sharing an end address.
+    loops: Vec<ActiveLoop>,
+    first: u32,
+    last: u32,
+    address: u32,
+    cycle: u64,
+    passes_left: u64,
+    finished: bool,
+}
+
+/// Returns every problem with the enabled loops of `sim` when playing `bank`,
+/// or an empty list.
+pub fn check_loops(sim: &Simulator, bank: MemoryBank) -> Vec<LoopError> {
+    let range = sim.loaded_pattern_range(bank);
+    if range.is_empty() {
+        return vec![LoopError::NothingLoaded];
+    }
+    let enabled: Vec<(usize, &PatternLoop)> =
+        sim.pattern_loops.iter().enumerate().filter(|(i, _)| sim.loop_enables >> i & 1 == 1).map(|(i, l)| (i + 1, l)).collect();
+
+    let mut errors = Vec::new();
+    for &(number, l) in &enabled {
+        let (start, end) = (l.start_address, l.end_address);
+        if end < start {
+            errors.push(LoopError::EndBeforeStart { number, start, end });
+        } else if !range.contains(&start) || !range.contains(&end) {
+            errors.push(LoopError::OutsideLoadedRange { number, start, end });
+        }
+        if l.count == 0 {
+            errors.push(LoopError::ZeroCount { number });
+        }
+    }
+    for (i, &(first, a)) in enabled.iter().enumerate() {
+        for &(second, b) in &enabled[i + 1..] {
+            let overlaps = a.start_address <= b.end_address && b.start_address <= a.end_address;
+            let nested = (a.start_address <= b.start_address && b.end_address <= a.end_address)
+                || (b.start_address <= a.start_address && a.end_address <= b.end_address);
+            if overlaps && !nested {
+                errors.push(LoopError::PartialOverlap { first, second });
+            }
+        }
+    }
+    errors
+}
+
+impl<'a> Sequencer<'a> {
+    /// Creates a sequencer for the active bank and loops loaded into `sim`, or
+    /// returns every loop misconfiguration found.
+    pub fn new(sim: &'a Simulator) -> Result<Self, Vec<LoopError>> {
+        Self::for_bank(sim, sim.active_bank)
+    }
+
+    /// Creates a sequencer that plays `bank` instead of the active bank.
+    pub fn for_bank(sim: &'a Simulator, bank: MemoryBank) -> Result<Self, Vec<LoopError>> {
+        let errors = check_loops(sim, bank);
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut loops: Vec<ActiveLoop> = sim
+            .pattern_loops
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| sim.loop_enables >> i & 1 == 1)
+            .map(|(_, l)| ActiveLoop { start: l.start_address, end: l.end_address, count: l.count, remaining: l.count })
+            .collect();
+        // A later start on the same end address is the inner loop.
+        loops.sort_by_key(|l| std::cmp::Reverse(l.start));
+
+        let range = sim.loaded_pattern_range(bank);
+        let repeat = (sim.repeat_count_2 as u64) << 32 | sim.repeat_count_1 as u64;
+        Ok(Self {
+            sim,
+            bank,
+            loops,
+            first: range.start,
+            last: range.end - 1,
+            address: range.start,
+            cycle: 0,
+            passes_left: repeat.max(1),
+            finished: false,
+        })
+    }
+
+    /// The length of each vector, in ticks of the main clock source.
+    pub fn vector_period(&self) -> u32 {
+        self.sim.main_clock_config.period()
+    }
+
+    // Moves to the address that follows the current one.
+    fn step(&mut self) {
+        for l in self.loops.iter_mut().filter(|l| l.end == self.address) {
+            if l.remaining > 1 {
+                l.remaining -= 1;
+                self.address = l.start;
+                return;
+            }
+            // Reload the count for the next time the loop is entered.
+            l.remaining = l.count;
+        }
+        if self.address < self.last {
+            self.address += 1;
+        } else {
+            self.passes_left -= 1;
+            self.finished = self.passes_left == 0;
+            self.address = self.first;
+        }
+    }
+}
+
+impl Iterator for Sequencer<'_> {
+    type Item = Vector;
+
+    fn next(&mut self) -> Option<Vector> {
+        if self.finished {
+            return None;
+        }
+        let (a, bank) = (self.address as usize, self.bank);
+        let fpgas = &self.sim.fpgas;
+        let second = self.sim.has_dual_fpga();
+        let vector = Vector {
+            cycle: self.cycle,
+            address: self.address,
+            pattern: [fpgas[0].pattern_memory(bank)[a], if second { fpgas[1].pattern_memory(bank)[a] } else { 0 }],
+            tristate: [fpgas[0].tristate_memory(bank)[a], if second { fpgas[1].tristate_memory(bank)[a] } else { 0 }],
+        };
+        self.cycle += 1;
+        self.step();
+        Some(vector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Loads addresses 1-8 with their own address as the pattern word.
+    fn loaded_simulator() -> Simulator {
+        let mut sim = Simulator::new(0x1F);
+        sim.process_command(b"<C1F5000>").unwrap();
+        sim.process_command(b"<P\x01\x00\x00\x00\x00\x02\x00\x00\x00\x00\x03\x00\x00\x00\x00\x04\x00\x00\x00\x00>").unwrap();
+        sim.process_command(b"<P\x05\x00\x00\x00\x00\x06\x00\x00\x00\x00\x07\x00\x00\x00\x00\x08\x00\x00\x00\x00>").unwrap();
+        sim.process_command(b"<C1F5001>").unwrap();
+        sim
+    }
+
+    fn addresses(sim: &Simulator) -> Vec<u32> {
+        Sequencer::new(sim).unwrap().map(|v| v.address).collect()
+    }
+
+    #[test]
+    fn plays_nested_loops_and_repeats() {
+        let mut sim = loaded_simulator();
+        sim.process_command(b"<C1F5002>").unwrap();
+        // Loop 1: 2-3 twice. Loop 2: 5-7 twice, containing loop 3: 6 three times.
+        sim.process_command(b"<Lxx01020302>").unwrap();
+        sim.process_command(b"<Lxx02020705>").unwrap();
+        sim.process_command(b"<Lxx03030606>").unwrap();
+        // Main clock period 0x0120, loops 1-3 enabled.
+        sim.process_command(b"<Xxx00002001007>").unwrap();
+        sim.process_command(b"<C1F5003>").unwrap();
+
+        assert_eq!(addresses(&sim), [1, 2, 3, 2, 3, 4, 5, 6, 6, 6, 7, 5, 6, 6, 6, 7, 8]);
+        let sequencer = Sequencer::new(&sim).unwrap();
+        assert_eq!(sequencer.vector_period(), 0x0120);
+        let last = sequencer.last().unwrap();
+        assert_eq!((last.cycle, last.pattern), (16, [8, 0]));
+
+        // Only loop 1, with the whole pattern played twice.
+        sim.loop_enables = 0b001;
+        sim.repeat_count_1 = 2;
+        assert_eq!(addresses(&sim), [1, 2, 3, 2, 3, 4, 5, 6, 7, 8, 1, 2, 3, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn plays_both_fpgas_with_tristate() {
+        let mut sim = loaded_simulator();
+        sim.fpgas[1].present = true;
+        sim.fpgas[1].pattern_memory_a[2] = 0xAA;
+        sim.fpgas[0].tristate_memory_a[2] = 0xF0;
+
+        let vector = Sequencer::new(&sim).unwrap().nth(1).unwrap();
+        assert_eq!(vector, Vector { cycle: 1, address: 2, pattern: [2, 0xAA], tristate: [0xF0, 0] });
+    }
+
+    #[test]
+    fn reports_loop_misconfigurations() {
+        assert_eq!(check_loops(&Simulator::new(0x1F), MemoryBank::A), [LoopError::NothingLoaded]);
+
+        let mut sim = loaded_simulator();
+        sim.pattern_loops[0] = PatternLoop { start_address: 5, end_address: 4, count: 2 };
+        sim.pattern_loops[1] = PatternLoop { start_address: 7, end_address: 9, count: 2 };
+        sim.pattern_loops[2] = PatternLoop { start_address: 1, end_address: 3, count: 0 };
+        sim.pattern_loops[3] = PatternLoop { start_address: 2, end_address: 6, count: 1 };
+        // Loop 5 is misconfigured but not enabled.
+        sim.pattern_loops[4] = PatternLoop { start_address: 9, end_address: 1, count: 0 };
+        sim.loop_enables = 0b01111;
+
+        let errors = Sequencer::new(&sim).unwrap_err();
+        assert_eq!(
+            errors,
+            [
+                LoopError::EndBeforeStart { number: 1, start: 5, end: 4 },
+                LoopError::OutsideLoadedRange { number: 2, start: 7, end: 9 },
+                LoopError::ZeroCount { number: 3 },
+                LoopError::PartialOverlap { first: 3, second: 4 },
+            ]
+        );
+        assert_eq!(errors[1].to_string(), "loop 2 (0x7..0x9) is outside the loaded pattern");
+    }
+
+    #[test]
+    fn plays_the_active_or_chosen_bank() {
+        let mut sim = loaded_simulator();
+        sim.select_load_bank(MemoryBank::B);
+        sim.process_command(b"<C1F5000>").unwrap();
+        sim.process_command(b"<P\x0A\x00\x00\x00\x00\x0B\x00\x00\x00\x00\x0C\x00\x00\x00\x00\x0D\x00\x00\x00\x00>").unwrap();
+        sim.process_command(b"<C1F5001>").unwrap();
+
+        let patterns = |s: Sequencer| s.map(|v| v.pattern[0]).collect::<Vec<_>>();
+        assert_eq!(patterns(Sequencer::new(&sim).unwrap()), [0x0A, 0x0B, 0x0C, 0x0D]);
+        assert_eq!(patterns(Sequencer::for_bank(&sim, MemoryBank::A).unwrap()), [1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+}
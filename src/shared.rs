@@ -0,0 +1,181 @@
+//! # Shared Simulator Handle
+//!
+//! The TUI, the serial listener and any other transport must all act on the same
+//! simulated board. `SharedSimulator` is a cheap, cloneable handle to a single
+//! `Simulator` guarded by a mutex, so every command is applied atomically and
+//! state changed from one source is immediately visible to all the others.
+//! `SharedBus` does the same for a whole multi-drop `Bus`.
+
+use crate::{Bus, CommandError, FrameDecoder, ProcessResult, Simulator};
+use std::{
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+/// Anything a transport can serve: a single board or a whole bus of them.
+pub trait Endpoint: Send {
+    /// Processes a single command frame and returns the response, if any.
+    fn process_command(&mut self, command_bytes: &[u8]) -> Result<ProcessResult, CommandError>;
+
+    /// Returns true if binary pattern frames should be decoded with the
+    /// two-FPGA layout.
+    fn has_dual_fpga(&self) -> bool;
+
+    /// Advances simulated time by `elapsed`.
+    fn advance(&mut self, elapsed: Duration);
+}
+
+impl Endpoint for Simulator {
+    fn process_command(&mut self, command_bytes: &[u8]) -> Result<ProcessResult, CommandError> {
+        Simulator::process_command(self, command_bytes)
+    }
+
+    fn has_dual_fpga(&self) -> bool {
+        Simulator::has_dual_fpga(self)
+    }
+
+    fn advance(&mut self, elapsed: Duration) {
+        Simulator::advance(self, elapsed)
+    }
+}
+
+/// A thread-safe handle to one authoritative endpoint.
+#[derive(Debug)]
+pub struct Shared<T> {
+    inner: Arc<Mutex<T>>,
+}
+
+/// A shared handle to a single board.
+pub type SharedSimulator = Shared<Simulator>;
+
+/// A shared handle to a multi-drop bus of boards.
+pub type SharedBus = Shared<Bus>;
+
+// Implemented by hand so cloning the handle never requires cloning the board.
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self { inner: self.inner.clone() }
+    }
+}
+
+impl<T> Shared<T> {
+    /// Wraps an endpoint so it can be shared between threads.
+    pub fn new(endpoint: T) -> Self {
+        Self { inner: Arc::new(Mutex::new(endpoint)) }
+    }
+
+    /// Locks the endpoint for direct access to its state.
+    ///
+    /// A panic on another thread while holding the lock does not make the board
+    /// unusable; the state is still returned as it was left.
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Endpoint> Shared<T> {
+    /// Processes a single command frame against the shared state.
+    pub fn process_command(&self, command_bytes: &[u8]) -> Result<ProcessResult, CommandError> {
+        self.lock().process_command(command_bytes)
+    }
+
+    /// Takes the next complete frame from `decoder` and processes it, returning
+    /// the frame with its result, or `None` if no complete frame is buffered.
+    ///
+    /// The pattern frame length depends on the board state, so it is decided
+    /// under the same lock as the command itself; another client cannot change
+    /// the layout between the two.
+    pub fn process_next_frame(
+        &self,
+        decoder: &mut FrameDecoder,
+    ) -> Option<(Vec<u8>, Result<ProcessResult, CommandError>)> {
+        let mut endpoint = self.lock();
+        let frame = decoder.next_frame(endpoint.has_dual_fpga())?;
+        let result = endpoint.process_command(&frame);
+        Some((frame, result))
+    }
+
+    /// Returns true if pattern frames should currently be decoded with the
+    /// two-FPGA layout.
+    pub fn has_dual_fpga(&self) -> bool {
+        self.lock().has_dual_fpga()
+    }
+
+    /// Advances simulated time by `elapsed`.
+    pub fn advance(&self, elapsed: Duration) {
+        self.lock().advance(elapsed)
+    }
+}
+
+impl<T> From<T> for Shared<T> {
+    fn from(endpoint: T) -> Self {
+        Self::new(endpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn clones_share_one_board() {
+        let shared = SharedSimulator::new(Simulator::new(0x1F));
+        let other = shared.clone();
+
+        other.process_command(b"<C1F03>").unwrap();
+        assert!(shared.lock().sequence_on);
+
+        shared.process_command(b"<C1F04>").unwrap();
+        assert!(!other.lock().sequence_on);
+    }
+
+    #[test]
+    fn processes_buffered_frames_one_at_a_time() {
+        let shared = SharedSimulator::new(Simulator::new(0x1F));
+        let mut decoder = FrameDecoder::new();
+        decoder.push(b"<C1F03><C1F0");
+
+        let (frame, result) = shared.process_next_frame(&mut decoder).unwrap();
+        assert_eq!(frame, b"<C1F03>");
+        assert!(result.is_ok());
+        assert!(shared.lock().sequence_on);
+        assert!(shared.process_next_frame(&mut decoder).is_none());
+
+        decoder.push(b"4>");
+        shared.process_next_frame(&mut decoder).unwrap().1.unwrap();
+        assert!(!shared.lock().sequence_on);
+    }
+
+    #[test]
+    fn commands_from_other_threads_are_visible() {
+        let shared = SharedSimulator::new(Simulator::new(0x1F));
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let handle = shared.clone();
+                thread::spawn(move || {
+                    let command = format!("<C1F090000{:05}{:05}>", i, i);
+                    handle.process_command(command.as_bytes()).unwrap();
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        // Whichever command ran last, both halves of the ID must come from the same one.
+        let sim = shared.lock();
+        assert_eq!(sim.prog_id_hint, sim.prog_id_lint);
+    }
+
+    #[test]
+    fn shared_bus_routes_by_address() {
+        let shared = SharedBus::new(Bus::with_addresses(&[0x1F, 0x20]));
+
+        shared.clone().process_command(b"<C2003>").unwrap();
+        let bus = shared.lock();
+        assert!(bus.board(0x20).unwrap().sequence_on);
+        assert!(!bus.board(0x1F).unwrap().sequence_on);
+    }
+}
@@ -0,0 +1,219 @@
+//! # State Snapshots
+//!
+//! Saves the complete state of a `Simulator` to a JSON file and restores it, so
+//! a long driver or pattern download can be resumed later or a board state that
+//! reproduces a bug can be handed to someone else. Pending events on the
+//! simulated clock and the internals of an open load session are included.
+//!
+//! FPGA memories are stored as runs of non-zero words (see `PatternMemory`).
+//! Every snapshot records `SNAPSHOT_VERSION`; fields missing from an older
+//! snapshot take their power-on values, and newer snapshots are rejected. A
+//! restored board must have the memory and table sizes of a new one and its
+//! pending events must name PSUs and steps that exist, so a hand-edited
+//! snapshot cannot leave it in a state later commands or events panic on.
+
+use crate::{MemoryBank, SimEvent, Simulator};
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path};
+
+/// The snapshot format version written by this build.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct SnapshotRef<'a> {
+    version: u32,
+    simulator: &'a Simulator,
+}
+
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+#[derive(Deserialize)]
+struct Snapshot {
+    simulator: Simulator,
+}
+
+/// Why a snapshot could not be saved or restored.
+#[derive(Debug)]
+pub enum SnapshotError {
+    /// The file could not be read or written.
+    Io(io::Error),
+    /// The file is not a valid snapshot.
+    Parse(serde_json::Error),
+    /// The snapshot was written by a newer format version.
+    UnsupportedVersion(u32),
+    /// The snapshot parses but describes a board the simulator cannot model.
+    Invalid(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "snapshot I/O failed: {}", e),
+            SnapshotError::Parse(e) => write!(f, "invalid snapshot: {}", e),
+            SnapshotError::UnsupportedVersion(v) => {
+                write!(f, "snapshot format version {} is newer than the supported version {}", v, SNAPSHOT_VERSION)
+            }
+            SnapshotError::Invalid(reason) => write!(f, "invalid snapshot: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl Simulator {
+    /// Serializes the full simulator state to a JSON snapshot.
+    pub fn to_snapshot(&self) -> String {
+        // Every field serializes to plain JSON, so this cannot fail.
+        serde_json::to_string(&SnapshotRef { version: SNAPSHOT_VERSION, simulator: self }).expect("simulator state serializes")
+    }
+
+    /// Restores a simulator from a snapshot produced by `to_snapshot`.
+    pub fn from_snapshot(text: &str) -> Result<Self, SnapshotError> {
+        let header: Header = serde_json::from_str(text).map_err(SnapshotError::Parse)?;
+        if header.version > SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(header.version));
+        }
+        let snapshot: Snapshot = serde_json::from_str(text).map_err(SnapshotError::Parse)?;
+        check_restored(&snapshot.simulator).map_err(SnapshotError::Invalid)?;
+        Ok(snapshot.simulator)
+    }
+
+    /// Writes a snapshot of the simulator to `path`.
+    pub fn save_snapshot<P: AsRef<Path>>(&self, path: P) -> Result<(), SnapshotError> {
+        Ok(fs::write(path, self.to_snapshot())?)
+    }
+
+    /// Reads a simulator back from a snapshot file.
+    pub fn load_snapshot<P: AsRef<Path>>(path: P) -> Result<Self, SnapshotError> {
+        Self::from_snapshot(&fs::read_to_string(path)?)
+    }
+}
+
+// Checks the sizes and indices that command handling relies on against a new board.
+fn check_restored(sim: &Simulator) -> Result<(), String> {
+    let new = Simulator::default();
+    let words = new.fpgas[0].pattern_memory_a.len();
+    for (f, fpga) in sim.fpgas.iter().enumerate() {
+        for bank in [MemoryBank::A, MemoryBank::B] {
+            for (name, memory) in [("pattern", fpga.pattern_memory(bank)), ("tristate", fpga.tristate_memory(bank))] {
+                if memory.len() != words {
+                    return Err(format!("FPGA {} {} memory {:?} has {} words, not {}", f + 1, name, bank, memory.len(), words));
+                }
+            }
+        }
+    }
+    if sim.fault_logs.len() != new.fault_logs.len() || sim.fault_log_index >= sim.fault_logs.len() {
+        return Err(format!("fault log index {} of {} logs, expected {} logs", sim.fault_log_index, sim.fault_logs.len(), new.fault_logs.len()));
+    }
+    if sim.amon_tests.len() != new.amon_tests.len() || sim.amon_test_count as usize > sim.amon_tests.len() {
+        return Err(format!("{} of {} AMON tests, expected {} tests", sim.amon_test_count, sim.amon_tests.len(), new.amon_tests.len()));
+    }
+    if sim.sram_address as usize > words || sim.pattern_end.iter().any(|&end| end as usize > words) {
+        return Err(format!("pattern load address beyond the {}-word memory", words));
+    }
+    for event in sim.clock.pending() {
+        let valid = match *event {
+            SimEvent::PsuStep { psu, step } => psu < sim.psus.len() && (1..=4).contains(&step),
+            SimEvent::PsuRamp { psu, .. } => psu < sim.psus.len(),
+            SimEvent::MonitorCycle | SimEvent::PtcPhase { .. } | SimEvent::AutoReset => true,
+        };
+        if !valid {
+            return Err(format!("pending event {:?} names a PSU or step the board does not have", event));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Fault;
+    use std::time::Duration;
+
+    #[test]
+    fn restored_simulator_resumes_a_load_session() {
+        let mut sim = Simulator::new(0x2A);
+        sim.process_command(b"<C2A5000>").unwrap();
+        sim.process_command(b"<P\x01\x02\x03\x04\x11\x05\x06\x07\x08\x22\x09\x0A\x0B\x0C\x33\x0D\x0E\x0F\x10\x44>").unwrap();
+        sim.fpgas[1].pattern_memory_b[0xFFFFF] = 7;
+        sim.inject_fault(Fault::DoorOpen).unwrap();
+        sim.advance(Duration::from_millis(250));
+
+        // Four 1M-word memories per FPGA would be tens of megabytes stored in full.
+        let text = sim.to_snapshot();
+        assert!(text.len() < 100_000, "snapshot is {} bytes", text.len());
+        let mut restored = Simulator::from_snapshot(&text).unwrap();
+
+        assert_eq!(restored.rs485_address, 0x2A);
+        assert_eq!(restored.now(), sim.now());
+        assert!(restored.is_pattern_loading());
+        assert!(restored.is_door_open());
+        assert_eq!(restored.fpgas[0].pattern_memory_a, sim.fpgas[0].pattern_memory_a);
+        assert_eq!(restored.fpgas[1].pattern_memory_b[0xFFFFF], 7);
+        assert_eq!(restored.fpgas[1].pattern_memory_b.allocated_pages(), 1);
+
+        // The session and its checksum carry on where the original left off.
+        for s in [&mut sim, &mut restored] {
+            s.process_command(b"<P\x21\x22\x23\x24\x55\x25\x26\x27\x28\x66\x29\x2A\x2B\x2C\x77\x2D\x2E\x2F\x30\x88>").unwrap();
+        }
+        let end = |s: &mut Simulator| s.process_command(b"<C2A5001>").unwrap().response;
+        assert_eq!(end(&mut restored), end(&mut sim));
+        assert_eq!(restored.fpgas[0].pattern_memory_a, sim.fpgas[0].pattern_memory_a);
+    }
+
+    #[test]
+    fn older_snapshots_fill_missing_fields_with_defaults() {
+        let restored = Simulator::from_snapshot(r#"{"version":1,"simulator":{"rs485_address":16,"bib_code":291}}"#).unwrap();
+        assert_eq!(restored.rs485_address, 0x10);
+        assert_eq!(restored.bib_code, 0x123);
+        assert_eq!(restored.fw_version, Simulator::new(0x10).fw_version);
+        assert_eq!(restored.fpgas[0].pattern_memory_a.len(), 0x100000);
+    }
+
+    #[test]
+    fn rejects_newer_or_malformed_snapshots() {
+        let newer = format!(r#"{{"version":{},"simulator":{{}}}}"#, SNAPSHOT_VERSION + 1);
+        assert!(matches!(Simulator::from_snapshot(&newer), Err(SnapshotError::UnsupportedVersion(_))));
+        assert!(matches!(Simulator::from_snapshot("{}"), Err(SnapshotError::Parse(_))));
+
+        let out_of_range = r#"{"version":1,"simulator":{"fpgas":[{"pattern_memory_a":{"len":4,"runs":[{"start":3,"words":[1,2]}]}},{}]}}"#;
+        assert!(matches!(Simulator::from_snapshot(out_of_range), Err(SnapshotError::Parse(_))));
+    }
+
+    #[test]
+    fn rejects_snapshots_that_would_panic_later() {
+        for simulator in [
+            r#"{"fpgas":[{},{"tristate_memory_b":{"len":0,"runs":[]}}]}"#,
+            r#"{"fault_logs":[]}"#,
+            r#"{"amon_tests":[]}"#,
+            r#"{"fault_log_index":10}"#,
+            r#"{"sram_address":2000000}"#,
+        ] {
+            let text = format!(r#"{{"version":1,"simulator":{}}}"#, simulator);
+            let error = Simulator::from_snapshot(&text).unwrap_err();
+            assert!(matches!(error, SnapshotError::Invalid(_)), "{}: {}", simulator, error);
+        }
+
+        // A hand-edited event for a seventh PSU would index past the PSUs when it fires.
+        let mut sim = Simulator::new(0x1F);
+        sim.clock.schedule(Duration::from_millis(5), SimEvent::PsuStep { psu: 0, step: 1 });
+        let text = sim.to_snapshot().replace(r#"{"PsuStep":{"psu":0,"step":1}}"#, r#"{"PsuStep":{"psu":6,"step":1}}"#);
+        assert!(matches!(Simulator::from_snapshot(&text), Err(SnapshotError::Invalid(_))));
+        let text = sim.to_snapshot().replace(r#"{"PsuStep":{"psu":0,"step":1}}"#, r#"{"PsuRamp":{"psu":9,"target":1.0,"remaining":2,"power_off":false}}"#);
+        assert!(matches!(Simulator::from_snapshot(&text), Err(SnapshotError::Invalid(_))));
+        assert!(Simulator::from_snapshot(&sim.to_snapshot()).is_ok());
+
+        // Left unchecked, a short fault log table divides by zero on the first trip.
+        let text = r#"{"version":1,"simulator":{"fault_logs":[]}}"#;
+        assert_eq!(Simulator::from_snapshot(text).unwrap_err().to_string(), "invalid snapshot: fault log index 0 of 0 logs, expected 10 logs");
+    }
+}
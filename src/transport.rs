@@ -0,0 +1,226 @@
+//! # Transports
+//!
+//! Serves the simulator protocol over byte streams. `serve` is the listener loop
+//! used by every transport: it reassembles frames with a `FrameDecoder`, applies
+//! them to a shared `Endpoint` (one board or a whole `Bus`) and writes any
+//! `#...#` response back to the peer.
+//!
+//! ## TCP interleaving policy
+//!
+//! `serve_tcp` accepts any number of sequential or concurrent connections, all
+//! acting on the same board. Each connection has its own decoder, so partial
+//! frames from different clients never mix. Complete frames are applied one at a
+//! time under the simulator lock in the order they arrive, and a response is only
+//! ever sent to the connection whose frame produced it. Everything else, including
+//! an open `C50` data-load session, is board state shared by all clients, just as
+//! it would be for several masters on one RS-485 bus.
+
+use crate::{Endpoint, FrameDecoder, Shared};
+use std::{
+    io::{self, Read, Write},
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Sender,
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+/// How long a blocking read waits before re-checking the stop flag.
+pub const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Messages sent from a listener thread to whoever is displaying its traffic.
+#[derive(Debug, Clone, PartialEq)]
+pub enum TransportMessage {
+    /// A line of traffic or status output.
+    Log(String),
+    /// A transport failure.
+    Error(String),
+}
+
+// The receiving side may already have gone away during shutdown; that is not an error here.
+fn send(tx: &Sender<TransportMessage>, message: TransportMessage) {
+    let _ = tx.send(message);
+}
+
+/// Runs the listener loop on a stream until `stop` is set or the peer closes it.
+///
+/// The stream should have a read timeout so the stop flag is checked regularly;
+/// reads that time out or would block are simply retried.
+pub fn serve<S: Read + Write, T: Endpoint>(
+    stream: &mut S,
+    simulator: &Shared<T>,
+    tx: &Sender<TransportMessage>,
+    stop: &AtomicBool,
+) -> io::Result<()> {
+    let mut read_buf = [0u8; 256];
+    let mut decoder = FrameDecoder::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        let bytes_read = match stream.read(&mut read_buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(ref e) if matches!(e.kind(), io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted) => continue,
+            Err(e) => return Err(e),
+        };
+
+        decoder.push(&read_buf[..bytes_read]);
+        // A single read may hold several frames, or only part of one.
+        while let Some((frame, result)) = simulator.process_next_frame(&mut decoder) {
+            send(tx, TransportMessage::Log(format!("> {}", frame.escape_ascii())));
+            match result {
+                Ok(result) => {
+                    for debug_log in result.logs {
+                        send(tx, TransportMessage::Log(debug_log));
+                    }
+                    if let Some(response) = result.response {
+                        send(tx, TransportMessage::Log(format!("< {}", response)));
+                        stream.write_all(response.as_bytes())?;
+                        stream.flush()?;
+                    }
+                }
+                Err(e) => send(tx, TransportMessage::Log(format!("[ERROR] {:?}", e))),
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Accepts TCP connections until `stop` is set, serving each one on its own thread.
+///
+/// See the module documentation for how concurrent clients are interleaved.
+pub fn serve_tcp<T: Endpoint + 'static>(
+    listener: TcpListener,
+    simulator: Shared<T>,
+    tx: Sender<TransportMessage>,
+    stop: Arc<AtomicBool>,
+) -> io::Result<()> {
+    // Poll for connections so the stop flag is honoured promptly.
+    listener.set_nonblocking(true)?;
+    let mut clients = Vec::new();
+
+    while !stop.load(Ordering::Relaxed) {
+        match listener.accept() {
+            Ok((mut stream, peer)) => {
+                stream.set_nonblocking(false)?;
+                stream.set_read_timeout(Some(READ_TIMEOUT))?;
+                send(&tx, TransportMessage::Log(format!("Client {} connected.", peer)));
+
+                let simulator = simulator.clone();
+                let tx = tx.clone();
+                let stop = stop.clone();
+                clients.push(thread::spawn(move || {
+                    match serve(&mut stream, &simulator, &tx, &stop) {
+                        Ok(()) => send(&tx, TransportMessage::Log(format!("Client {} disconnected.", peer))),
+                        Err(e) => send(&tx, TransportMessage::Error(format!("Client {}: {}", peer, e))),
+                    }
+                }));
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => thread::sleep(READ_TIMEOUT),
+            Err(e) => return Err(e),
+        }
+        clients.retain(|client| !client.is_finished());
+    }
+
+    for client in clients {
+        let _ = client.join();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SharedSimulator, Simulator};
+    use std::{
+        io::{BufRead, BufReader},
+        net::TcpStream,
+        sync::mpsc,
+    };
+
+    /// An in-memory stream that replays scripted reads and records writes.
+    struct ScriptedStream {
+        reads: Vec<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl Read for ScriptedStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.reads.is_empty() {
+                return Ok(0);
+            }
+            let chunk = self.reads.remove(0);
+            buf[..chunk.len()].copy_from_slice(&chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    impl Write for ScriptedStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn read_response(reader: &mut BufReader<TcpStream>) -> String {
+        let mut response = Vec::new();
+        // Responses are framed as "#...#": read up to the opening and closing markers.
+        reader.read_until(b'#', &mut response).unwrap();
+        reader.read_until(b'#', &mut response).unwrap();
+        String::from_utf8(response).unwrap()
+    }
+
+    #[test]
+    fn serve_answers_frames_split_across_reads() {
+        let simulator = SharedSimulator::new(Simulator::new(0x1F));
+        let (tx, rx) = mpsc::channel();
+        let mut stream = ScriptedStream {
+            reads: vec![b"<C1F".to_vec(), b"03><C1F22>".to_vec()],
+            written: Vec::new(),
+        };
+
+        serve(&mut stream, &simulator, &tx, &AtomicBool::new(false)).unwrap();
+
+        assert_eq!(stream.written, b"#ON##00000,00000#".to_vec());
+        assert!(simulator.lock().sequence_on);
+        assert_eq!(rx.try_recv().unwrap(), TransportMessage::Log("> <C1F03>".to_string()));
+    }
+
+    #[test]
+    fn tcp_clients_share_one_board() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let simulator = SharedSimulator::new(Simulator::new(0x1F));
+        let (tx, _rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let server = {
+            let simulator = simulator.clone();
+            let stop = stop.clone();
+            thread::spawn(move || serve_tcp(listener, simulator, tx, stop))
+        };
+
+        let mut first = BufReader::new(TcpStream::connect(address).unwrap());
+        let mut second = BufReader::new(TcpStream::connect(address).unwrap());
+
+        first.get_mut().write_all(b"<C1F03>").unwrap();
+        assert_eq!(read_response(&mut first), "#ON#");
+
+        // The second client observes the state changed by the first.
+        second.get_mut().write_all(b"<C1F17>").unwrap();
+        let reference = read_response(&mut second);
+        assert_eq!(reference.split(',').nth(7), Some("1"));
+
+        drop(first);
+        drop(second);
+        stop.store(true, Ordering::Relaxed);
+        server.join().unwrap().unwrap();
+        assert!(simulator.lock().sequence_on);
+    }
+}
@@ -0,0 +1,179 @@
+//! # VCD Export
+//!
+//! Writes a range of loaded pattern memory as a Value Change Dump, so what the
+//! host downloaded through `P` and `R` frames can be inspected in a waveform
+//! viewer such as GTKWave and compared with the test-program source.
+//!
+//! Either memory bank can be exported. Each pattern address becomes one vector
+//! of `period_ns`. Every output bit is its own signal, `out0` to `out31` under
+//! an `fpga1` scope, plus an `fpga2` scope when a second FPGA is fitted. A set
+//! bit in the tristate memory means the pin is disabled, so it is dumped as
+//! high-Z (`z`).
+
+use crate::{Fpga, MemoryBank, Simulator};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::Path,
+};
+
+/// Output bits per FPGA pattern word.
+const BITS_PER_WORD: usize = 32;
+
+// Builds a short VCD identifier from the printable characters '!' to '~'.
+fn identifier(mut index: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            return id;
+        }
+        index -= 1;
+    }
+}
+
+// The value of one output pin at one address.
+fn pin_value(fpga: &Fpga, bank: MemoryBank, address: usize, bit: usize) -> char {
+    if fpga.tristate_memory(bank)[address] >> bit & 1 == 1 {
+        'z'
+    } else if fpga.pattern_memory(bank)[address] >> bit & 1 == 1 {
+        '1'
+    } else {
+        '0'
+    }
+}
+
+/// Writes pattern addresses `addresses` of `bank` in `sim` as a VCD to `out`,
+/// one vector per address lasting `period_ns` nanoseconds.
+pub fn write_vcd<W: Write>(sim: &Simulator, bank: MemoryBank, addresses: Range<usize>, period_ns: u64, out: &mut W) -> io::Result<()> {
+    let fpgas: &[Fpga] = if sim.has_dual_fpga() { &sim.fpgas } else { &sim.fpgas[..1] };
+    // Every memory read below must hold the whole range, not just FPGA1's pattern memory.
+    let memory_len = fpgas
+        .iter()
+        .flat_map(|fpga| [fpga.pattern_memory(bank).len(), fpga.tristate_memory(bank).len()])
+        .min()
+        .unwrap_or(0);
+    if addresses.is_empty() || addresses.end > memory_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("address range {:#X}..{:#X} is empty or beyond the {:#X}-word memory", addresses.start, addresses.end, memory_len),
+        ));
+    }
+
+    writeln!(out, "$version Endzone 250 Simulator pattern export $end")?;
+    writeln!(out, "$timescale 1ns $end")?;
+    for (f, _) in fpgas.iter().enumerate() {
+        writeln!(out, "$scope module fpga{} $end", f + 1)?;
+        for bit in 0..BITS_PER_WORD {
+            writeln!(out, "$var wire 1 {} out{} $end", identifier(f * BITS_PER_WORD + bit), bit)?;
+        }
+        writeln!(out, "$upscope $end")?;
+    }
+    writeln!(out, "$enddefinitions $end")?;
+
+    let mut previous: Vec<char> = Vec::new();
+    for (n, address) in addresses.clone().enumerate() {
+        let values: Vec<char> =
+            fpgas.iter().flat_map(|fpga| (0..BITS_PER_WORD).map(move |bit| pin_value(fpga, bank, address, bit))).collect();
+        writeln!(out, "#{}", n as u64 * period_ns)?;
+        if n == 0 {
+            writeln!(out, "$dumpvars")?;
+        }
+        for (i, &value) in values.iter().enumerate() {
+            if previous.get(i) != Some(&value) {
+                writeln!(out, "{}{}", value, identifier(i))?;
+            }
+        }
+        if n == 0 {
+            writeln!(out, "$end")?;
+        }
+        previous = values;
+    }
+    // Close the last vector so it shows for a full period.
+    writeln!(out, "#{}", addresses.len() as u64 * period_ns)
+}
+
+/// Writes pattern addresses `addresses` of `bank` in `sim` to a VCD file at `path`.
+pub fn export_vcd<P: AsRef<Path>>(sim: &Simulator, bank: MemoryBank, addresses: Range<usize>, period_ns: u64, path: P) -> io::Result<()> {
+    let mut out = BufWriter::new(File::create(path)?);
+    write_vcd(sim, bank, addresses, period_ns, &mut out)?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PatternMemory;
+
+    fn vcd(sim: &Simulator, addresses: Range<usize>) -> String {
+        let mut out = Vec::new();
+        write_vcd(sim, MemoryBank::A, addresses, 100, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn dumps_changes_and_high_z_pins() {
+        let mut sim = Simulator::new(0x1F);
+        sim.process_command(b"<C1F5000>").unwrap();
+        // Addresses 1-4: bit 0 toggles, bit 1 stays high.
+        sim.process_command(b"<P\x03\x00\x00\x00\x00\x02\x00\x00\x00\x00\x03\x00\x00\x00\x00\x02\x00\x00\x00\x00>").unwrap();
+        sim.process_command(b"<C1F5001>").unwrap();
+        // Disable bit 0 at address 3.
+        sim.fpgas[0].tristate_memory_a[3] = 1;
+
+        let text = vcd(&sim, 1..5);
+        assert!(text.contains("$var wire 1 ! out0 $end\n$var wire 1 \" out1 $end\n"));
+        assert!(!text.contains("fpga2"));
+        assert!(text.contains("#0\n$dumpvars\n1!\n1\"\n0#\n"));
+        let changes = text.split("$end\n#100\n").nth(1).unwrap();
+        assert_eq!(changes, "0!\n#200\nz!\n#300\n0!\n#400\n");
+    }
+
+    #[test]
+    fn splits_signals_across_two_fpgas() {
+        let mut sim = Simulator::new(0x1F);
+        sim.fpgas[1].present = true;
+        sim.fpgas[1].pattern_memory_a[1] = 1 << 31;
+
+        let text = vcd(&sim, 1..2);
+        assert!(text.contains("$scope module fpga2 $end\n$var wire 1 A out0 $end\n"));
+        // Signal 63 is FPGA2 out31.
+        assert!(text.contains(&format!("\n1{}\n", identifier(63))));
+        assert!(text.ends_with("$end\n#100\n"));
+    }
+
+    #[test]
+    fn exports_the_chosen_bank() {
+        let mut sim = Simulator::new(0x1F);
+        sim.select_load_bank(MemoryBank::B);
+        sim.process_command(b"<C1F5000>").unwrap();
+        sim.process_command(b"<P\x01\x00\x00\x00\x00\x01\x00\x00\x00\x00\x01\x00\x00\x00\x00\x01\x00\x00\x00\x00>").unwrap();
+        sim.process_command(b"<C1F5001>").unwrap();
+
+        let mut out = Vec::new();
+        write_vcd(&sim, MemoryBank::B, 1..2, 100, &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("$dumpvars\n1!\n"));
+        // Bank A was never loaded.
+        assert!(vcd(&sim, 1..2).contains("$dumpvars\n0!\n"));
+    }
+
+    #[test]
+    fn identifiers_are_unique_and_ranges_are_checked() {
+        let ids: std::collections::HashSet<String> = (0..10_000).map(identifier).collect();
+        assert_eq!(ids.len(), 10_000);
+        assert!(write_vcd(&Simulator::new(0x1F), MemoryBank::A, 5..5, 100, &mut Vec::new()).is_err());
+        assert!(write_vcd(&Simulator::new(0x1F), MemoryBank::A, 0..0x100001, 100, &mut Vec::new()).is_err());
+        assert!(write_vcd(&Simulator::new(0x1F), MemoryBank::B, 0..0x100001, 100, &mut Vec::new()).is_err());
+
+        // A shorter FPGA2 or tristate memory limits the range too.
+        let mut sim = Simulator::new(0x1F);
+        sim.fpgas[1].present = true;
+        sim.fpgas[1].pattern_memory_a = PatternMemory::new(0x10);
+        assert!(write_vcd(&sim, MemoryBank::A, 0..0x10, 100, &mut Vec::new()).is_ok());
+        assert!(write_vcd(&sim, MemoryBank::A, 0..0x11, 100, &mut Vec::new()).is_err());
+        sim.fpgas[0].tristate_memory_b = PatternMemory::new(0x10);
+        assert!(write_vcd(&sim, MemoryBank::B, 0..0x11, 100, &mut Vec::new()).is_err());
+    }
+}